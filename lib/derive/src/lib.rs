@@ -0,0 +1,472 @@
+//! `#[derive(Layout)]` for the course types of murmelbahn-lib.
+//!
+//! deku generates the parser from the `#[deku]` attributes but offers no way to inspect them at
+//! runtime. This derive reads the same attributes and implements
+//! `murmelbahn_lib::app::layout::Layout`, which describes the on-disk layout as data. Attributes
+//! that change the layout in a way the description can't express are a compile error, so the
+//! description and the parser can't drift apart.
+//!
+//! `#[deku_derive]` removes temporary fields and all `#[deku]` attributes before any derive runs,
+//! on those structs `#[derive(Layout)]` has to come before `#[deku_derive]`.
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{ToTokens, quote};
+use syn::{
+    Attribute, Data, DataEnum, DataStruct, DeriveInput, Error, Expr, Fields, GenericArgument,
+    Ident, LitStr, Pat, PathArguments, Type, UnOp, parse_macro_input,
+};
+
+/// The `#[deku]` attributes understood on structs and enums.
+const CONTAINER_KEYS: &[&str] = &["ctx", "id", "id_type"];
+
+/// The `#[deku]` attributes understood on fields. `temp_value`, `default` and `writer` only
+/// matter for writing or for fields that are not read.
+const FIELD_KEYS: &[&str] = &[
+    "count",
+    "cond",
+    "ctx",
+    "default",
+    "map",
+    "skip",
+    "temp",
+    "temp_value",
+    "writer",
+];
+
+/// The `#[deku]` attributes understood on enum variants.
+const VARIANT_KEYS: &[&str] = &["id", "id_pat"];
+
+#[proc_macro_derive(Layout, attributes(deku))]
+pub fn derive_layout(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "Layout can't be derived for generic types",
+        ));
+    }
+    let attributes = Attributes::parse(&input.attrs, CONTAINER_KEYS)?;
+    let layout = match &input.data {
+        Data::Struct(data) => structure(input, &attributes, data)?,
+        Data::Enum(data) if attributes.get("id").is_some() => switch(&attributes, data)?,
+        Data::Enum(data) => enumeration(input, &attributes, data)?,
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                input,
+                "Layout can't be derived for unions",
+            ));
+        }
+    };
+    let name = &input.ident;
+    Ok(quote! {
+        impl crate::app::layout::Layout for #name {
+            fn layout() -> crate::app::layout::Type {
+                #layout
+            }
+        }
+    })
+}
+
+/// The `#[deku]` attributes of an item.
+#[derive(Default)]
+struct Attributes {
+    values: HashMap<String, LitStr>,
+    flags: Vec<String>,
+}
+
+impl Attributes {
+    fn parse(attrs: &[Attribute], allowed: &[&str]) -> syn::Result<Attributes> {
+        let mut attributes = Attributes::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("deku")) {
+            attr.parse_nested_meta(|meta| {
+                let key = meta
+                    .path
+                    .get_ident()
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                if !allowed.contains(&key.as_str()) {
+                    return Err(meta.error(format!(
+                        "#[deku({key})] is not supported by the layout description, \
+                         teach murmelbahn-derive about it"
+                    )));
+                }
+                if meta.input.peek(syn::Token![=]) {
+                    attributes.values.insert(key, meta.value()?.parse()?);
+                } else {
+                    attributes.flags.push(key);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(attributes)
+    }
+
+    fn get(&self, key: &str) -> Option<&LitStr> {
+        self.values.get(key)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|present| present == flag)
+    }
+}
+
+/// The context of a struct from `#[deku(ctx = "version: CourseSaveDataVersion")]`.
+struct Context {
+    name: Ident,
+    ty: Type,
+}
+
+impl Context {
+    fn parse(attributes: &Attributes) -> syn::Result<Option<Context>> {
+        let Some(ctx) = attributes.get("ctx") else {
+            return Ok(None);
+        };
+        let argument: syn::PatType = ctx.parse_with(|input: syn::parse::ParseStream| {
+            let pat = Pat::parse_single(input)?;
+            let colon = input.parse()?;
+            let ty = input.parse()?;
+            Ok(syn::PatType {
+                attrs: Vec::new(),
+                pat: Box::new(pat),
+                colon_token: colon,
+                ty: Box::new(ty),
+            })
+        })?;
+        let Pat::Ident(name) = *argument.pat else {
+            return Err(Error::new_spanned(
+                ctx,
+                "only a single named context is supported",
+            ));
+        };
+        Ok(Some(Context {
+            name: name.ident,
+            ty: *argument.ty,
+        }))
+    }
+}
+
+fn structure(
+    input: &DeriveInput,
+    attributes: &Attributes,
+    data: &DataStruct,
+) -> syn::Result<TokenStream> {
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            input,
+            "Layout can only be derived for structs with named fields",
+        ));
+    };
+    let context = Context::parse(attributes)?;
+    let mut read = Vec::new();
+    let mut layouts = Vec::new();
+    for field in &fields.named {
+        let attributes = Attributes::parse(&field.attrs, FIELD_KEYS)?;
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        if attributes.has("skip") {
+            continue;
+        }
+        layouts.push(field_layout(
+            ident,
+            &field.ty,
+            &attributes,
+            &read,
+            context.as_ref(),
+        )?);
+        read.push(ident.clone());
+    }
+    let name = input.ident.to_string();
+    Ok(quote! {
+        crate::app::layout::Type::Struct(|| crate::app::layout::Struct {
+            module: module_path!(),
+            name: #name,
+            fields: vec![#(#layouts),*],
+        })
+    })
+}
+
+/// The layout of a field, `read` are the fields read before it.
+fn field_layout(
+    ident: &Ident,
+    ty: &Type,
+    attributes: &Attributes,
+    read: &[Ident],
+    context: Option<&Context>,
+) -> syn::Result<TokenStream> {
+    let name = ident.to_string();
+
+    if let Some(count) = attributes.get("count") {
+        let count = earlier_field(count, read)?;
+        if attributes.get("cond").is_some()
+            || attributes
+                .get("ctx")
+                .is_some_and(|ctx| !forwards(ctx, context))
+        {
+            return Err(Error::new_spanned(
+                ident,
+                "lists with a condition or their own context are not supported",
+            ));
+        }
+        if is(ty, "String") {
+            let Some(map) = attributes.get("map") else {
+                return Err(Error::new_spanned(
+                    ident,
+                    "strings need a `map` decoding their bytes",
+                ));
+            };
+            let map: Expr = map.parse()?;
+            return Ok(quote! {
+                crate::app::layout::Field::String {
+                    name: #name,
+                    count: #count,
+                    decode: |bytes| (#map)(bytes).map(drop),
+                }
+            });
+        }
+        if attributes.get("map").is_some() {
+            return Err(Error::new_spanned(ident, "mapped lists are not supported"));
+        }
+        let Some(item) = argument(ty, "Vec") else {
+            return Err(Error::new_spanned(ty, "lists have to be a `Vec`"));
+        };
+        return Ok(quote! {
+            crate::app::layout::Field::List {
+                name: #name,
+                count: #count,
+                ty: <#item as crate::app::layout::Layout>::layout(),
+            }
+        });
+    }
+
+    let cond = attributes.get("cond");
+    // Conditional fields are an `Option` of the value read, mapped fields show the type the
+    // value is mapped to (e.g. `Option<i32>` for a retainer id that may be missing).
+    let ty = if cond.is_some() {
+        argument(ty, "Option")
+            .ok_or_else(|| Error::new_spanned(ty, "conditional fields have to be an `Option`"))?
+    } else if attributes.get("map").is_some() {
+        argument(ty, "Option").unwrap_or(ty)
+    } else {
+        ty
+    };
+    let mapped = match attributes.get("map") {
+        Some(map) => {
+            let map: Expr = map.parse()?;
+            quote! {
+                Some(|reader| {
+                    let raw = ::deku::DekuReader::from_reader_with_ctx(reader, ())?;
+                    (#map)(raw).map(drop)
+                })
+            }
+        }
+        None => quote!(None),
+    };
+    let version = match attributes.get("ctx") {
+        Some(ctx) if !forwards(ctx, context) => {
+            let Expr::Field(source) = ctx.parse()? else {
+                return Err(Error::new_spanned(
+                    ctx,
+                    "a context has to be the context of the struct or a field read before",
+                ));
+            };
+            let source = source.to_token_stream().to_string().replace(' ', "");
+            quote!(Some(#source))
+        }
+        _ => quote!(None),
+    };
+    let value = quote! {
+        crate::app::layout::Field::Value {
+            name: #name,
+            ty: <#ty as crate::app::layout::Layout>::layout(),
+            mapped: #mapped,
+            version: #version,
+        }
+    };
+
+    match cond {
+        Some(cond) => {
+            let condition = condition(cond, read, context)?;
+            Ok(quote! {
+                crate::app::layout::Field::If {
+                    condition: #condition,
+                    fields: vec![#value],
+                }
+            })
+        }
+        None => Ok(value),
+    }
+}
+
+/// Whether `ctx` passes on the context of the struct unchanged.
+fn forwards(ctx: &LitStr, context: Option<&Context>) -> bool {
+    context.is_some_and(|context| {
+        ctx.parse::<Ident>()
+            .is_ok_and(|ident| ident == context.name)
+    })
+}
+
+/// The name of a field read before, e.g. the count of a list.
+fn earlier_field(value: &LitStr, read: &[Ident]) -> syn::Result<String> {
+    match value.parse::<Ident>() {
+        Ok(ident) if read.contains(&ident) => Ok(ident.to_string()),
+        _ => Err(Error::new_spanned(
+            value,
+            "this has to be the name of a field read before",
+        )),
+    }
+}
+
+/// A `cond` is either a `bool` field read before or a function of the version in the context.
+fn condition(cond: &LitStr, read: &[Ident], context: Option<&Context>) -> syn::Result<TokenStream> {
+    let expr: Expr = cond.parse()?;
+    let flag = match &expr {
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Deref(_)) => &*unary.expr,
+        expr => expr,
+    };
+    if let Expr::Path(path) = flag {
+        if let Some(ident) = path.path.get_ident().filter(|ident| read.contains(ident)) {
+            let flag = ident.to_string();
+            return Ok(quote!(crate::app::layout::Condition::Flag(#flag)));
+        }
+    }
+    let Some(Context { name, ty }) = context else {
+        return Err(Error::new_spanned(
+            cond,
+            "a condition has to be a field read before or a function of the context",
+        ));
+    };
+    Ok(quote!(crate::app::layout::Condition::versions(|#name: #ty| #expr)))
+}
+
+/// An enum stored as its discriminant.
+fn enumeration(
+    input: &DeriveInput,
+    attributes: &Attributes,
+    data: &DataEnum,
+) -> syn::Result<TokenStream> {
+    if attributes
+        .get("id_type")
+        .is_none_or(|id_type| id_type.value() != "u32")
+    {
+        return Err(Error::new_spanned(
+            input,
+            "only enums with #[deku(id_type = \"u32\")] are supported",
+        ));
+    }
+    let mut names = Vec::new();
+    let mut ids = Vec::new();
+    let mut catch_all = false;
+    for variant in &data.variants {
+        let attributes = Attributes::parse(&variant.attrs, VARIANT_KEYS)?;
+        if let Some(id_pat) = attributes.get("id_pat") {
+            if id_pat.value() != "_" || variant.fields.len() != 1 {
+                return Err(Error::new_spanned(
+                    variant,
+                    "the only supported pattern is a catch-all `_` keeping the discriminant",
+                ));
+            }
+            catch_all = true;
+            continue;
+        }
+        if !variant.fields.is_empty() {
+            return Err(Error::new_spanned(variant, "variants can't have fields"));
+        }
+        let id: Expr = match (attributes.get("id"), &variant.discriminant) {
+            (Some(id), _) => id.parse()?,
+            (None, Some((_, discriminant))) => discriminant.clone(),
+            (None, None) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "variants need an #[deku(id = \"...\")] or a discriminant",
+                ));
+            }
+        };
+        names.push(variant.ident.to_string());
+        ids.push(id);
+    }
+    let name = input.ident.to_string();
+    Ok(quote! {
+        crate::app::layout::Type::Enum(crate::app::layout::Enum {
+            module: module_path!(),
+            name: #name,
+            variants: vec![#((#names, #ids)),*],
+            catch_all: #catch_all,
+        })
+    })
+}
+
+/// An enum of which the variant is chosen by the version in the context, not stored in the file.
+fn switch(attributes: &Attributes, data: &DataEnum) -> syn::Result<TokenStream> {
+    let context = Context::parse(attributes)?;
+    let id = attributes.get("id").expect("checked by the caller");
+    if !context.is_some_and(|context| id.parse::<Ident>().is_ok_and(|id| id == context.name)) {
+        return Err(Error::new_spanned(
+            id,
+            "enums without an `id_type` have to choose their variant by the context",
+        ));
+    }
+    let mut cases = Vec::new();
+    for variant in &data.variants {
+        let attributes = Attributes::parse(&variant.attrs, VARIANT_KEYS)?;
+        let (Some(id_pat), Fields::Unnamed(fields)) = (attributes.get("id_pat"), &variant.fields)
+        else {
+            return Err(Error::new_spanned(
+                variant,
+                "variants need an #[deku(id_pat = \"...\")] and a single unnamed field",
+            ));
+        };
+        let [field] = fields.unnamed.iter().collect::<Vec<_>>()[..] else {
+            return Err(Error::new_spanned(variant, "variants need a single field"));
+        };
+        // The field gets the version matched by `id_pat` as its context
+        Attributes::parse(&field.attrs, &["ctx"])?;
+        let versions = match id_pat.parse_with(Pat::parse_multi)? {
+            Pat::Or(or) => or.cases.into_iter().collect(),
+            pat => vec![pat],
+        };
+        if let Some(pat) = versions.iter().find(|pat| !matches!(pat, Pat::Path(_))) {
+            return Err(Error::new_spanned(
+                pat,
+                "only paths to versions are supported",
+            ));
+        }
+        let ty = &field.ty;
+        cases.push(quote! {
+            (vec![#(#versions),*], <#ty as crate::app::layout::Layout>::layout())
+        });
+    }
+    Ok(quote! {
+        crate::app::layout::Type::Switch(vec![#(#cases),*])
+    })
+}
+
+/// Whether `ty` is the type named `name`.
+fn is(ty: &Type, name: &str) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident(name))
+}
+
+/// The type argument of `ty` if it is `name<T>`, e.g. `T` of `Vec<T>`.
+fn argument<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
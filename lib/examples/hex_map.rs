@@ -0,0 +1,26 @@
+//! Prints the hex grids of a course with their tiles and rails, see
+//! `murmelbahn_lib::app::hexmap`.
+//!
+//! cargo run -p murmelbahn-lib --example hex_map -- <course-file> [--ascii]
+
+use murmelbahn_lib::app::course::SavedCourse;
+use murmelbahn_lib::app::hexmap::{self, Charset};
+use murmelbahn_lib::app::scene::Scene;
+use std::env;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().expect("usage: hex_map <course-file> [--ascii]");
+    let charset = match args.next().as_deref() {
+        Some("--ascii") => Charset::Ascii,
+        _ => Charset::Unicode,
+    };
+    let course = SavedCourse::from_path(&path).expect("parse course");
+
+    // Draw as much as can be resolved, a broken course is what this is for
+    let (scene, errors) = Scene::resolve(&course.course);
+    for error in errors {
+        eprintln!("{error}");
+    }
+    print!("{}", hexmap::map(&scene, charset));
+}
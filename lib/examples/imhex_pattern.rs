@@ -0,0 +1,7 @@
+//! Prints the ImHex pattern for course files, see `murmelbahn_lib::app::imhex`.
+//!
+//! cargo run -p murmelbahn-lib --example imhex_pattern > murmelbahn.hexpat
+
+fn main() {
+    print!("{}", murmelbahn_lib::app::imhex::pattern());
+}
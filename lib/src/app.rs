@@ -5,12 +5,28 @@
 //! As such we use this module to process the data and then convert it into a more useful form.
 pub mod bom;
 pub mod course;
+pub mod diagnostics;
 pub mod download;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod gltf;
+pub mod graph;
+pub mod hex;
+pub mod hexmap;
+pub mod imhex;
+pub mod initiallaunch;
 pub mod layer;
+pub mod layout;
+pub mod marbles;
+pub mod partial;
 pub mod pillar;
+pub mod pose;
 pub mod power2022;
 pub mod rail;
+pub mod scene;
 pub mod skytrax;
+pub mod svg;
+pub mod validation;
 pub mod wall;
 pub mod ziplineadded2019;
 
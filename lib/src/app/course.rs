@@ -1,23 +1,51 @@
-use std::cmp::max;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 use deku::prelude::*;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use snafu::prelude::*;
+use tracing::warn;
 
-use crate::app::{power2022, skytrax, ziplineadded2019};
+use crate::app::diagnostics::{self, Diagnostics};
+use crate::app::hex::CubeVector;
+use crate::app::layout::Layout;
+use crate::app::partial::PartialCourse;
+use crate::app::{initiallaunch, power2022, skytrax, ziplineadded2019};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Failed to deserialize course: {source}"))]
-    DeserializationFailedError { source: DekuError },
+    #[snafu(display(
+        "Failed to deserialize course{}: {source}",
+        diagnostics.as_ref().map(|diagnostics| format!(" {diagnostics}")).unwrap_or_default()
+    ))]
+    DeserializationFailedError {
+        source: DekuError,
+        /// Where the course failed to parse, `None` if it couldn't be determined.
+        diagnostics: Option<Box<Diagnostics>>,
+    },
+
+    #[snafu(display("Failed to serialize course: {source}"))]
+    SerializationFailedError { source: DekuError },
 
     #[snafu(display("Failed to read file [{path:?}]"))]
     IoError { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to write file [{path:?}]"))]
+    WriteError { path: PathBuf, source: io::Error },
+}
+
+impl Error {
+    /// Where parsing failed, only available for [`Error::DeserializationFailedError`].
+    pub fn diagnostics(&self) -> Option<&Diagnostics> {
+        match self {
+            Error::DeserializationFailedError { diagnostics, .. } => diagnostics.as_deref(),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Clone, Debug, DekuRead, Serialize)]
+#[derive(Clone, Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(id_type = "u32")]
 pub enum CourseKind {
     None = 0,
@@ -33,7 +61,7 @@ pub enum CourseKind {
     PowerEditorial = 10,
 }
 
-#[derive(Clone, Debug, DekuRead, Serialize)]
+#[derive(Clone, Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(id_type = "u32")]
 pub enum ObjectiveKind {
     #[deku(id = "0")]
@@ -46,7 +74,7 @@ pub enum ObjectiveKind {
     Unknown(u32),
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(id_type = "u32")]
 pub enum CourseElementGeneration {
     #[deku(id = "0")]
@@ -85,7 +113,7 @@ pub enum CourseElementGeneration {
     Unknown(u32),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
 pub enum Direction {
     NorthEast,
     East,
@@ -96,20 +124,67 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn hex_rotation_to_direction(hex_rotation: i32) -> Direction {
-        match hex_rotation {
+    pub const ALL: [Direction; 6] = [
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::East => Direction::West,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::West => Direction::East,
+            Direction::NorthWest => Direction::SouthEast,
+        }
+    }
+
+    /// The hex rotation pointing in this direction, the inverse of
+    /// [`Direction::hex_rotation_to_direction`].
+    pub fn hex_rotation(&self) -> i32 {
+        match self {
+            Direction::East => 0,
+            Direction::SouthEast => 1,
+            Direction::SouthWest => 2,
+            Direction::West => 3,
+            Direction::NorthWest => 4,
+            Direction::NorthEast => 5,
+        }
+    }
+
+    /// The direction after turning by a number of hex rotations (60° steps).
+    pub fn rotated(&self, hex_rotation: i32) -> Direction {
+        match (self.hex_rotation() + hex_rotation).rem_euclid(6) {
             0 => Direction::East,
             1 => Direction::SouthEast,
             2 => Direction::SouthWest,
             3 => Direction::West,
             4 => Direction::NorthWest,
-            5 => Direction::NorthEast,
-            _ => panic!("Invalid hex rotation"),
+            _ => Direction::NorthEast,
+        }
+    }
+
+    /// Returns `None` for rotations outside of `0..6`.
+    pub fn hex_rotation_to_direction(hex_rotation: i32) -> Option<Direction> {
+        match hex_rotation {
+            0 => Some(Direction::East),
+            1 => Some(Direction::SouthEast),
+            2 => Some(Direction::SouthWest),
+            3 => Some(Direction::West),
+            4 => Some(Direction::NorthWest),
+            5 => Some(Direction::NorthEast),
+            _ => None,
         }
     }
 }
 
-#[derive(Clone, Debug, DekuRead, Serialize)]
+/// A position on the hex grid, see [`hex`](crate::app::hex) for the axes and geometry on them.
+#[derive(Clone, Debug, DekuRead, DekuWrite, Eq, Hash, Layout, PartialEq, Serialize)]
 pub struct HexVector {
     pub y: i32,
     pub x: i32,
@@ -142,26 +217,89 @@ impl HexVector {
         self.hex_vector_in_distance(direction, 1)
     }
 
-    /// This expects world coordinates
+    /// The number of steps between two hexes, this expects world coordinates
     pub fn distance(&self, to: &HexVector) -> i32 {
-        // Distance between the two on each axis
-        let dx = (self.x - to.x).abs();
-        let dy = (self.y - to.y).abs();
+        CubeVector::from(self)
+            .subtract(&CubeVector::from(to))
+            .length()
+    }
+}
+
+/// A corner of a cell, SkyTrax pieces are joined at these.
+///
+/// Three cells meet at every corner. A vertex is the sum of their positions, which keeps it on
+/// the same axes as [`HexVector`] (at three times the scale). Every vertex is either the
+/// [`Corner::North`] or the [`Corner::South`] corner of exactly one cell, sums that are neither
+/// are not a vertex.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct VertexVector {
+    pub y: i32,
+    pub x: i32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum Corner {
+    /// The corner between the [`Direction::NorthWest`] and [`Direction::NorthEast`] neighbors.
+    North,
+    /// The corner between the [`Direction::SouthEast`] and [`Direction::SouthWest`] neighbors.
+    South,
+}
+
+impl VertexVector {
+    /// The vertex at a `corner` of `cell`.
+    pub fn new(cell: &HexVector, corner: Corner) -> VertexVector {
+        let [first, second] = corner.neighbors();
+        let neighbors = cell.neighbor(&first).add(&cell.neighbor(&second));
+        VertexVector {
+            y: cell.y + neighbors.y,
+            x: cell.x + neighbors.x,
+        }
+    }
+
+    /// The cell this is the corner of and which one, `None` if this is not a vertex.
+    pub fn corner(&self) -> Option<(HexVector, Corner)> {
+        [Corner::North, Corner::South]
+            .into_iter()
+            .find_map(|corner| {
+                // Taking away the offset of the corner leaves three times the position of the cell
+                let offset = VertexVector::new(&HexVector::new(0, 0), corner);
+                let (x, y) = (self.x - offset.x, self.y - offset.y);
+                (x % 3 == 0 && y % 3 == 0).then(|| (HexVector::new(x / 3, y / 3), corner))
+            })
+    }
+
+    /// The three cells meeting at this vertex, `None` if this is not a vertex.
+    pub fn cells(&self) -> Option<[HexVector; 3]> {
+        let (cell, corner) = self.corner()?;
+        let [first, second] = corner.neighbors();
+        Some([cell.neighbor(&first), cell.neighbor(&second), cell])
+    }
+}
 
-        dx + max(0, dy - dx)
+impl Corner {
+    fn neighbors(&self) -> [Direction; 2] {
+        match self {
+            Corner::North => [Direction::NorthWest, Direction::NorthEast],
+            Corner::South => [Direction::SouthEast, Direction::SouthWest],
+        }
     }
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Clone, Debug, Serialize)]
 pub struct CourseMetaData {
     pub creation_timestamp: u64,
 
-    #[deku(temp)]
+    #[deku(
+        temp,
+        temp_value = "u8::try_from(title.len()).map_err(|_| DekuError::InvalidParam(format!(\"Title is longer than 255 bytes: {title}\").into()))?"
+    )]
     size: u8,
 
     #[deku(count = "size")]
     #[deku(map = "CourseMetaData::decode_title")]
+    #[deku(writer = "title.as_bytes().to_writer(deku::writer, ())")]
     pub title: String,
 
     pub order_number: i32,
@@ -174,7 +312,7 @@ pub struct CourseMetaData {
 impl CourseMetaData {
     /// This tries to decode the original title that was used by the creator in the App.
     /// As this should always come from the App directly it _should_ not fail.
-    fn decode_title(bytes: Vec<u8>) -> Result<String, DekuError> {
+    pub(crate) fn decode_title(bytes: Vec<u8>) -> Result<String, DekuError> {
         std::str::from_utf8(&bytes)
             .map(|title| title.to_string())
             .map_err(|source| {
@@ -185,11 +323,48 @@ impl CourseMetaData {
     }
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+/// Length of the sha256 checksum the App appends to every course file.
+const CHECKSUM_LENGTH: usize = 32;
+
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 pub struct SavedCourse {
     pub header: SaveDataHeader,
     #[deku(ctx = "header.version")]
     pub course: Course,
+
+    /// Whether the checksum at the end of the file matches the course data.
+    /// This is not part of the course data itself, it is set by [`SavedCourse::from_bytes`].
+    #[deku(skip, default = "Integrity::Absent")]
+    pub integrity: Integrity,
+}
+
+/// The result of checking the sha256 checksum that the App appends to every course file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum Integrity {
+    /// The checksum matches the course data.
+    Valid,
+
+    /// There is data after the course but it is not the checksum of the course data.
+    /// The file was corrupted or edited by hand, so anything derived from it (e.g. the bill of
+    /// materials) might be wrong.
+    Mismatch,
+
+    /// The file ends right after the course data.
+    Absent,
+}
+
+impl Integrity {
+    /// Checks the `trailer` (everything after the course data) against the `payload`
+    /// (the header and course data).
+    pub fn check(payload: &[u8], trailer: &[u8]) -> Integrity {
+        if trailer.is_empty() {
+            Integrity::Absent
+        } else if trailer.len() == CHECKSUM_LENGTH && Sha256::digest(payload)[..] == *trailer {
+            Integrity::Valid
+        } else {
+            Integrity::Mismatch
+        }
+    }
 }
 
 impl SavedCourse {
@@ -202,25 +377,77 @@ impl SavedCourse {
 
     /// Reads a serialized course from the provided bytes.
     ///
-    /// Note: This does not take an offset or return the rest as it wasn't required for my use-case.
-    /// Such a method could be included easily if needed.
+    /// If this fails the error contains [`Diagnostics`] on where exactly it failed.
+    ///
+    /// Everything after the course data is treated as the checksum the App appends,
+    /// the result of checking it is available in [`SavedCourse::integrity`].
     pub fn from_bytes(bytes: &[u8]) -> Result<SavedCourse, Error> {
-        Ok(<SavedCourse as DekuContainerRead>::from_bytes((bytes, 0))
-            .context(DeserializationFailedSnafu)?
-            .1)
+        let ((rest, _), mut course) = <SavedCourse as DekuContainerRead>::from_bytes((bytes, 0))
+            .with_context(|_| DeserializationFailedSnafu {
+                diagnostics: diagnostics::locate_failure(bytes).map(Box::new),
+            })?;
+
+        let payload = &bytes[..bytes.len() - rest.len()];
+        course.integrity = Integrity::check(payload, rest);
+        if course.integrity == Integrity::Mismatch {
+            warn!(
+                "Checksum of course [{}] does not match its contents",
+                course.course.meta_data().title
+            );
+        }
+        Ok(course)
+    }
+
+    /// Reads a serialized course from the provided bytes like [`SavedCourse::from_bytes`] but
+    /// returns everything that could be read (e.g. the header and metadata) if the course can't
+    /// be parsed fully.
+    ///
+    /// This is useful for courses from a newer app release which changed the layout of the body.
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<SavedCourse, Box<PartialCourse>> {
+        SavedCourse::from_bytes(bytes).map_err(|error| Box::new(PartialCourse::read(bytes, error)))
+    }
+
+    /// Writes this course to a `Path` in the format used by the App.
+    pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let bytes = self.to_bytes()?;
+        fs::write(path, bytes).context(WriteSnafu { path })
+    }
+
+    /// Serializes this course into the binary format used by the App.
+    ///
+    /// The App appends a sha256 checksum of everything before it (header and course) to the file,
+    /// this is calculated from the serialized data and appended here as well.
+    /// That means the result always has a valid checksum, no matter what [`SavedCourse::integrity`] is.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = <SavedCourse as DekuContainerWrite>::to_bytes(self)
+            .context(SerializationFailedSnafu)?;
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum);
+        Ok(bytes)
     }
 }
 
 /// A `Course` is the main entry point.
 ///
 /// There are multiple versions of courses which have been added over the years.
-/// Anything older than 2019 (`ZiplineAdded2019`) is not currently supported.
 /// Only courses since 2020 (`Pro2020` and `LightStones2023`) have any meaningful support besides showing their contents.
 /// This is because most courses that have been created are 2020 or newer.
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion", id = "version")]
 #[serde(untagged)]
 pub enum Course {
+    #[deku(id_pat = "CourseSaveDataVersion::InitialLaunch")]
+    InitialLaunch(#[deku(ctx = "CourseSaveDataVersion::InitialLaunch")] initiallaunch::Course),
+
+    #[deku(id_pat = "CourseSaveDataVersion::RailRework2018")]
+    RailRework2018(#[deku(ctx = "CourseSaveDataVersion::RailRework2018")] initiallaunch::Course),
+
+    #[deku(id_pat = "CourseSaveDataVersion::PersistenceRefactor2019")]
+    PersistenceRefactor2019(
+        #[deku(ctx = "CourseSaveDataVersion::PersistenceRefactor2019")] initiallaunch::Course,
+    ),
+
     #[deku(id_pat = "CourseSaveDataVersion::ZiplineAdded2019")]
     ZiplineAdded2019(
         #[deku(ctx = "CourseSaveDataVersion::ZiplineAdded2019")] ziplineadded2019::Course,
@@ -235,6 +462,16 @@ pub enum Course {
     #[deku(id_pat = "CourseSaveDataVersion::LightStones2023")]
     LightStones2023(#[deku(ctx = "CourseSaveDataVersion::LightStones2023")] power2022::Course),
 
+    /// Only written by beta releases of the app while SkyTrax was in development.
+    ///
+    /// The layout is assumed to be the same as [`Course::SkyTrax`]. This has not been checked
+    /// against a real course file: the test course of this version is assembled by hand from the
+    /// same assumption, and `tests/parse_all.rs` reports it as unverified until the corpus has a
+    /// course of this version. If the layout differs, reading it fails and
+    /// [`SavedCourse::from_bytes_lenient`] still returns what was read before.
+    #[deku(id_pat = "CourseSaveDataVersion::PreSkyTraxDuringDevelopment")]
+    PreSkyTrax(#[deku(ctx = "CourseSaveDataVersion::PreSkyTraxDuringDevelopment")] skytrax::Course),
+
     #[deku(id_pat = "CourseSaveDataVersion::SkyTrax")]
     SkyTrax(#[deku(ctx = "CourseSaveDataVersion::SkyTrax")] skytrax::Course),
 }
@@ -242,15 +479,18 @@ pub enum Course {
 impl Course {
     pub fn meta_data(&self) -> CourseMetaData {
         match self {
+            Course::InitialLaunch(course)
+            | Course::RailRework2018(course)
+            | Course::PersistenceRefactor2019(course) => course.meta_data.clone(),
             Course::ZiplineAdded2019(course) => course.meta_data.clone(),
             Course::Power2022(course) | Course::Pro2020(course) => course.meta_data.clone(),
             Course::LightStones2023(course) => course.meta_data.clone(),
-            Course::SkyTrax(course) => course.meta_data.clone(),
+            Course::PreSkyTrax(course) | Course::SkyTrax(course) => course.meta_data.clone(),
         }
     }
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 pub struct SaveDataHeader {
     pub guid: u128,
     pub version: CourseSaveDataVersion,
@@ -269,7 +509,7 @@ impl SaveDataHeader {
 }
 
 // Copy needed for deku magic
-#[derive(Clone, Copy, Debug, DekuRead, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, DekuRead, DekuWrite, Layout, PartialEq, Serialize)]
 #[deku(id_type = "u32")]
 pub enum CourseSaveDataVersion {
     InitialLaunch = 100101,
@@ -284,17 +524,33 @@ pub enum CourseSaveDataVersion {
 }
 
 impl CourseSaveDataVersion {
-    /// Whether the parser can decode a course *body* of this version. The older
-    /// pre-2019 versions are recognised in the header but have no `Course`
-    /// implementation, so a course of one of those versions parses its header
-    /// but not its body.
+    /// Every known version in the order they were introduced.
+    pub const ALL: [CourseSaveDataVersion; 9] = [
+        Self::InitialLaunch,
+        Self::RailRework2018,
+        Self::PersistenceRefactor2019,
+        Self::ZiplineAdded2019,
+        Self::Pro2020,
+        Self::Power2022,
+        Self::LightStones2023,
+        Self::PreSkyTraxDuringDevelopment,
+        Self::SkyTrax,
+    ];
+
+    /// Whether the parser can decode a course *body* of this version.
+    /// Every version known today has a `Course` implementation, a version that is added here
+    /// without one parses its header but not its body.
     pub fn is_supported(self) -> bool {
         matches!(
             self,
-            Self::ZiplineAdded2019
+            Self::InitialLaunch
+                | Self::RailRework2018
+                | Self::PersistenceRefactor2019
+                | Self::ZiplineAdded2019
                 | Self::Pro2020
                 | Self::Power2022
                 | Self::LightStones2023
+                | Self::PreSkyTraxDuringDevelopment
                 | Self::SkyTrax
         )
     }
@@ -303,6 +559,7 @@ impl CourseSaveDataVersion {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::fixtures::{RawCourse, skytrax_course, zipline_course};
 
     /// The version sits right after the 16-byte guid. Reading it raw lets us
     /// identify a course's version even when the body layout changed and the
@@ -326,14 +583,15 @@ mod tests {
         assert!(CourseSaveDataVersion::ZiplineAdded2019.is_supported());
         assert!(CourseSaveDataVersion::Power2022.is_supported());
         assert!(CourseSaveDataVersion::LightStones2023.is_supported());
-        assert!(!CourseSaveDataVersion::InitialLaunch.is_supported());
-        assert!(!CourseSaveDataVersion::PersistenceRefactor2019.is_supported());
+        assert!(CourseSaveDataVersion::InitialLaunch.is_supported());
+        assert!(CourseSaveDataVersion::RailRework2018.is_supported());
+        assert!(CourseSaveDataVersion::PersistenceRefactor2019.is_supported());
     }
 
-    /// SkyTrax (version 7) is decodable. The development-only version 6 that
-    /// precedes it is recognised in the header but has no parser.
+    /// SkyTrax (version 7) is decodable and so is the development-only version 6
+    /// that precedes it.
     #[test]
-    fn skytrax_is_supported_but_its_predecessor_is_not() {
+    fn skytrax_and_its_predecessor_are_supported() {
         let bytes = 7u32.to_le_bytes();
         let (_, sky) = CourseSaveDataVersion::from_bytes((&bytes, 0)).unwrap();
         assert_eq!(sky, CourseSaveDataVersion::SkyTrax);
@@ -342,6 +600,125 @@ mod tests {
         let bytes = 6u32.to_le_bytes();
         let (_, pre) = CourseSaveDataVersion::from_bytes((&bytes, 0)).unwrap();
         assert_eq!(pre, CourseSaveDataVersion::PreSkyTraxDuringDevelopment);
-        assert!(!pre.is_supported());
+        assert!(pre.is_supported());
+    }
+
+    #[test]
+    fn distances_count_every_step() {
+        let from = HexVector::new(2, -1);
+        for direction in Direction::ALL {
+            for steps in 0..4 {
+                let to = from.hex_vector_in_distance(&direction, steps);
+                assert_eq!(from.distance(&to), steps, "{direction:?}");
+                assert_eq!(to.distance(&from), steps, "{direction:?}");
+            }
+        }
+        // Two steps north west and one west, the old formula only counted the two steps along x
+        let to = from
+            .hex_vector_in_distance(&Direction::NorthWest, 2)
+            .neighbor(&Direction::West);
+        assert_eq!(from.distance(&to), 3);
+    }
+
+    /// Every corner is shared by three cells that are all next to each other.
+    #[test]
+    fn vertices_know_their_cells() {
+        let cell = HexVector::new(4, -2);
+        let north = VertexVector::new(&cell, Corner::North);
+        let (corner_of, corner) = north.corner().unwrap();
+        assert_eq!((corner_of.y, corner_of.x, corner), (-2, 4, Corner::North));
+
+        let cells = north.cells().unwrap();
+        let north_east = cell.neighbor(&Direction::NorthEast);
+        assert!(
+            cells
+                .iter()
+                .any(|c| (c.y, c.x) == (north_east.y, north_east.x))
+        );
+        for (a, b) in [(0, 1), (1, 2), (0, 2)] {
+            assert_eq!(cells[a].distance(&cells[b]), 1);
+        }
+
+        let south = VertexVector::new(&cell, Corner::South);
+        assert_ne!(south, north);
+        assert_eq!(south.corner().unwrap().1, Corner::South);
+
+        // Three times the position of a cell is its center, not a corner
+        assert!(VertexVector { y: -6, x: 12 }.cells().is_none());
+    }
+
+    /// The SkyTrax course is written back byte for byte, including the trailing checksum.
+    #[test]
+    fn skytrax_course_round_trips() {
+        let bytes = skytrax_course().with_checksum();
+
+        let course = SavedCourse::from_bytes(&bytes).expect("hand-assembled course parses");
+        let Course::SkyTrax(body) = &course.course else {
+            panic!("expected a SkyTrax course");
+        };
+        assert_eq!(body.meta_data.title, "Round trip");
+        assert_eq!(body.connectors.len(), 2);
+        assert_eq!(course.integrity, Integrity::Valid);
+
+        assert_eq!(course.to_bytes().expect("course serializes"), bytes);
+    }
+
+    /// The ZiplineAdded2019 course is written back byte for byte.
+    #[test]
+    fn zipline_course_round_trips() {
+        let bytes = zipline_course().with_checksum();
+
+        let course = SavedCourse::from_bytes(&bytes).expect("hand-assembled course parses");
+        assert!(matches!(course.course, Course::ZiplineAdded2019(_)));
+
+        assert_eq!(course.to_bytes().expect("course serializes"), bytes);
+    }
+
+    /// A minimal Power2022 course without anything on it.
+    fn empty_power_course() -> RawCourse {
+        RawCourse::default()
+            .header(4)
+            .meta_data("Empty")
+            .u32(0) // layers
+            .u32(0) // rails
+            .u32(0) // pillars
+            .u32(8) // generation: Power
+            .i32(0) // walls
+    }
+
+    #[test]
+    fn checksum_is_verified() {
+        let bytes = empty_power_course().with_checksum();
+        let course = SavedCourse::from_bytes(&bytes).unwrap();
+        assert_eq!(course.integrity, Integrity::Valid);
+    }
+
+    /// A course edited after the checksum was written is flagged.
+    #[test]
+    fn checksum_mismatch_is_flagged() {
+        let mut bytes = empty_power_course().with_checksum();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        let course = SavedCourse::from_bytes(&bytes).unwrap();
+        assert_eq!(course.integrity, Integrity::Mismatch);
+
+        // A trailer that isn't even a checksum is a mismatch as well
+        let bytes = empty_power_course().bytes(&[1, 2, 3]).0;
+        let course = SavedCourse::from_bytes(&bytes).unwrap();
+        assert_eq!(course.integrity, Integrity::Mismatch);
+    }
+
+    #[test]
+    fn missing_checksum_is_absent() {
+        let bytes = empty_power_course().0;
+        let course = SavedCourse::from_bytes(&bytes).unwrap();
+        assert_eq!(course.integrity, Integrity::Absent);
+
+        // Writing it back adds a valid checksum
+        let written = course.to_bytes().unwrap();
+        assert_eq!(
+            SavedCourse::from_bytes(&written).unwrap().integrity,
+            Integrity::Valid
+        );
     }
 }
@@ -0,0 +1,464 @@
+//! Locates where in a course file parsing failed.
+//!
+//! The errors deku returns only say _what_ went wrong (e.g. an unknown enum value or too few
+//! bytes) but not _where_. When a course fails to parse we walk it a second time, field by field
+//! following the [`layout`] description, and keep track of the byte offset and the path to the
+//! field (e.g. `course.layers[3].cells[12].tree_node_data.children[0].construction_data.kind`).
+//!
+//! The tests check that this walk consumes exactly as many bytes as the regular parse for every
+//! format, which also keeps the layout description in line with the parser.
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+use deku::ctx::Limit;
+use deku::prelude::*;
+use deku::reader::Reader;
+use serde::Serialize;
+
+use crate::app::course::CourseSaveDataVersion;
+use crate::app::layout::{self, Condition, Field, Primitive, Struct, Type};
+
+/// How many bytes to show before and after the failing offset.
+const WINDOW_CONTEXT: usize = 32;
+
+/// Where parsing a course failed.
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostics {
+    /// The offset of the first byte of the field that failed to parse.
+    pub offset: usize,
+
+    /// The path to the field that failed to parse, e.g.
+    /// `course.layers[3].cells[12].tree_node_data.index`.
+    pub path: String,
+
+    /// The bytes around `offset`.
+    pub window: HexWindow,
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at byte {} in [{}]", self.offset, self.path)
+    }
+}
+
+/// A few bytes of a course file around an offset of interest.
+///
+/// Displays as a hex dump with sixteen bytes per row where the byte at `offset` is marked.
+#[derive(Clone, Debug, Serialize)]
+pub struct HexWindow {
+    /// The offset of the first byte in `bytes`, always a multiple of 16.
+    pub start: usize,
+    pub bytes: Vec<u8>,
+    /// The offset (in the whole file, not in `bytes`) of the byte to mark.
+    pub offset: usize,
+}
+
+impl HexWindow {
+    pub fn new(bytes: &[u8], offset: usize) -> HexWindow {
+        let start = offset.saturating_sub(WINDOW_CONTEXT) / 16 * 16;
+        let end = bytes.len().min(offset + WINDOW_CONTEXT);
+        HexWindow {
+            start,
+            bytes: bytes.get(start..end).unwrap_or_default().to_vec(),
+            offset,
+        }
+    }
+}
+
+impl Display for HexWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (row, chunk) in self.bytes.chunks(16).enumerate() {
+            let row_start = self.start + row * 16;
+            write!(f, "{row_start:08X} ")?;
+            for (column, byte) in chunk.iter().enumerate() {
+                let marker = if row_start + column == self.offset {
+                    '>'
+                } else {
+                    ' '
+                };
+                write!(f, "{marker}{byte:02X}")?;
+            }
+            writeln!(f)?;
+        }
+        // The failure is at the end of the file (e.g. a truncated course)
+        if self.offset >= self.start + self.bytes.len() {
+            writeln!(f, "{:08X} >(end of file)", self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks `bytes` field by field and returns where parsing fails.
+///
+/// Returns `None` if the walk succeeds, which means that `bytes` _can_ be parsed.
+pub(crate) fn locate_failure(bytes: &[u8]) -> Option<Diagnostics> {
+    let failure = walk(bytes).err()?;
+    Some(Diagnostics {
+        offset: failure.offset,
+        path: failure.path,
+        window: HexWindow::new(bytes, failure.offset),
+    })
+}
+
+/// Walks `bytes` field by field, returns the number of bytes that make up the course.
+fn walk(bytes: &[u8]) -> Result<usize, Failure> {
+    let mut cursor = Cursor::new(bytes);
+    let mut tracer = Tracer {
+        reader: Reader::new(&mut cursor),
+        path: Vec::new(),
+        scopes: Vec::new(),
+        version: None,
+    };
+    tracer.structure(&layout::saved_course())?;
+    Ok(tracer.offset())
+}
+
+#[derive(Debug)]
+struct Failure {
+    offset: usize,
+    path: String,
+}
+
+/// The counts, flags and enum values read in a struct by the name of their field, values read in
+/// nested structs are prefixed with the name of the struct field (e.g. `header.version`).
+type Scope = HashMap<String, i64>;
+
+struct Tracer<'a, 'b> {
+    reader: Reader<'a, Cursor<&'b [u8]>>,
+    path: Vec<String>,
+    /// One scope per struct being walked.
+    scopes: Vec<Scope>,
+    version: Option<CourseSaveDataVersion>,
+}
+
+type Traced<T> = Result<T, Failure>;
+
+impl Tracer<'_, '_> {
+    fn offset(&self) -> usize {
+        self.reader.bits_read / 8
+    }
+
+    /// The failure to report for `field` at the current offset.
+    fn failure(&self, field: &str) -> Failure {
+        let mut path = self.path.clone();
+        path.push(field.to_string());
+        Failure {
+            offset: self.offset(),
+            path: path.join("."),
+        }
+    }
+
+    /// The failure to report if reading `field` at the current offset fails.
+    fn fail(&self, field: &str) -> impl FnOnce(DekuError) -> Failure + use<> {
+        let failure = self.failure(field);
+        move |_| failure
+    }
+
+    fn read<T>(&mut self, field: &str) -> Traced<T>
+    where
+        T: for<'c> DekuReader<'c>,
+    {
+        let fail = self.fail(field);
+        T::from_reader_with_ctx(&mut self.reader, ()).map_err(fail)
+    }
+
+    /// Looks up a count, flag or enum value read earlier in the current struct.
+    fn lookup(&self, field: &str) -> Traced<i64> {
+        self.scopes
+            .last()
+            .and_then(|scope| scope.get(field))
+            .copied()
+            .ok_or_else(|| self.failure(field))
+    }
+
+    /// Looks up a count read earlier in the current struct, `name` is the list or string it is
+    /// the count of.
+    fn count(&self, count: &str, name: &str) -> Traced<usize> {
+        let count = self.lookup(count)?;
+        usize::try_from(count).map_err(|_| self.failure(name))
+    }
+
+    fn structure(&mut self, structure: &Struct) -> Traced<Scope> {
+        self.scopes.push(Scope::new());
+        let result = self.fields(&structure.fields);
+        let scope = self.scopes.pop().unwrap_or_default();
+        result.map(|()| scope)
+    }
+
+    fn fields(&mut self, fields: &[Field]) -> Traced<()> {
+        for field in fields {
+            match field {
+                Field::Value {
+                    name,
+                    ty,
+                    mapped,
+                    version,
+                } => {
+                    if let Some(version) = version {
+                        let raw = self.lookup(version)?;
+                        let version = u32::try_from(raw)
+                            .ok()
+                            .and_then(|raw| {
+                                CourseSaveDataVersion::try_from(raw.to_le_bytes().as_slice()).ok()
+                            })
+                            .ok_or_else(|| self.failure(name))?;
+                        self.version = Some(version);
+                    }
+                    let values = match mapped {
+                        Some(mapped) => {
+                            let fail = self.fail(name);
+                            mapped(&mut self.reader).map_err(fail)?;
+                            Vec::new()
+                        }
+                        None => self.value(name, ty)?,
+                    };
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.extend(values);
+                    }
+                }
+                Field::List { name, count, ty } => {
+                    let count = self.count(count, name)?;
+                    for index in 0..count {
+                        self.value(&format!("{name}[{index}]"), ty)?;
+                    }
+                }
+                Field::String {
+                    name,
+                    count,
+                    decode,
+                } => {
+                    let count = self.count(count, name)?;
+                    let fail = self.fail(name);
+                    Vec::<u8>::from_reader_with_ctx(
+                        &mut self.reader,
+                        (Limit::new_count(count), ()),
+                    )
+                    .and_then(decode)
+                    .map_err(fail)?;
+                }
+                Field::If { condition, fields } => {
+                    let holds = match condition {
+                        Condition::Version(versions) => self
+                            .version
+                            .is_some_and(|version| versions.contains(&version)),
+                        Condition::Flag(flag) => self.lookup(flag)? != 0,
+                    };
+                    if holds {
+                        self.fields(fields)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a value, returns the counts, flags and enum values read by their name.
+    fn value(&mut self, name: &str, ty: &Type) -> Traced<Vec<(String, i64)>> {
+        match ty {
+            Type::Primitive(primitive) => {
+                let value = match primitive {
+                    Primitive::U8 => Some(i64::from(self.read::<u8>(name)?)),
+                    Primitive::U32 => Some(i64::from(self.read::<u32>(name)?)),
+                    Primitive::I32 => Some(i64::from(self.read::<i32>(name)?)),
+                    Primitive::Bool => Some(i64::from(self.read::<bool>(name)?)),
+                    Primitive::U64 => self.read::<u64>(name).map(|_| None)?,
+                    Primitive::U128 => self.read::<u128>(name).map(|_| None)?,
+                    Primitive::F32 => self.read::<f32>(name).map(|_| None)?,
+                };
+                Ok(value
+                    .map(|value| (name.to_string(), value))
+                    .into_iter()
+                    .collect())
+            }
+            Type::Enum(enumeration) => {
+                let failure = self.failure(name);
+                let raw = self.read::<u32>(name)?;
+                if enumeration.accepts(raw) {
+                    Ok(vec![(name.to_string(), i64::from(raw))])
+                } else {
+                    Err(failure)
+                }
+            }
+            Type::Struct(structure) => {
+                self.path.push(name.to_string());
+                let result = self.structure(&structure());
+                self.path.pop();
+                Ok(result?
+                    .into_iter()
+                    .map(|(field, value)| (format!("{name}.{field}"), value))
+                    .collect())
+            }
+            Type::Switch(cases) => {
+                let ty = self
+                    .version
+                    .and_then(|version| {
+                        cases
+                            .iter()
+                            .find(|(versions, _)| versions.contains(&version))
+                    })
+                    .map(|(_, ty)| ty)
+                    .ok_or_else(|| self.failure(name))?;
+                self.value(name, ty)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use deku::reader::Reader;
+
+    use super::{HexWindow, Tracer, walk};
+    use crate::app::course::SavedCourse;
+    use crate::app::fixtures::{RawCourse, legacy_course, skytrax_course, zipline_course};
+    use crate::app::layout::{Field, Layout, Struct};
+
+    /// A LightStones2023 course with one tile carrying the given power signal mode and a wall
+    /// with one balcony.
+    fn light_stones_course(power_signal_mode: u32) -> RawCourse {
+        RawCourse::default()
+            .header(5)
+            .meta_data("Light")
+            .u32(1) // layers
+            .i32(0)
+            .u32(0) // base layer piece
+            .f32(-0.2)
+            .i32(0)
+            .i32(0)
+            .i32(1) // cells
+            .i32(0)
+            .i32(0)
+            .i32(0) // index
+            .i32(0) // children
+            .u32(1) // starter
+            .i32(0)
+            .i32(0)
+            .i32(-2_147_483_647) // no retainer id
+            .u32(power_signal_mode)
+            .u32(2_147_483_648) // no light stone colour
+            .u32(0) // rails
+            .u32(0) // pillars
+            .u32(9) // generation: Autumn2023
+            .i32(1) // walls
+            .i32(0)
+            .i32(0)
+            .i32(0)
+            .i32(0)
+            .i32(1)
+            .i32(0)
+            .i32(1) // balconies
+            .i32(2000)
+            .u32(1) // east
+            .i32(1)
+            .i32(3)
+            .u8(0)
+    }
+
+    /// The walk has to consume exactly the bytes the regular parse does, for every format.
+    #[test]
+    fn walk_mirrors_the_parser() {
+        let pre_skytrax = {
+            let mut raw = skytrax_course();
+            raw.0[16..20].copy_from_slice(&6_u32.to_le_bytes());
+            raw
+        };
+        for bytes in [
+            skytrax_course().with_checksum(),
+            pre_skytrax.with_checksum(),
+            zipline_course().with_checksum(),
+            light_stones_course(2).with_checksum(),
+            legacy_course(100101),
+            legacy_course(1),
+        ] {
+            SavedCourse::from_bytes(&bytes).expect("hand-assembled course parses");
+            // Everything but the checksum
+            assert_eq!(walk(&bytes).expect("walk succeeds"), bytes.len() - 32);
+        }
+    }
+
+    #[test]
+    fn invalid_enum_value_is_located() {
+        let bytes = light_stones_course(7).with_checksum();
+
+        let error = SavedCourse::from_bytes(&bytes).expect_err("power signal mode 7 is invalid");
+        let diagnostics = error.diagnostics().expect("failure is located");
+        assert_eq!(diagnostics.offset, 111);
+        assert_eq!(
+            diagnostics.path,
+            "course.layer_construction_data[0].cell_construction_datas[0].tree_node_data.construction_data.power_signal_mode"
+        );
+        assert_eq!(
+            diagnostics.window.bytes[diagnostics.offset - diagnostics.window.start],
+            7
+        );
+        assert!(
+            error
+                .to_string()
+                .contains("at byte 111 in [course.layer_construction_data[0]")
+        );
+    }
+
+    #[test]
+    fn unknown_version_is_located() {
+        let bytes = RawCourse::default().header(8).meta_data("Future").0;
+
+        let error = SavedCourse::from_bytes(&bytes).expect_err("version 8 is unknown");
+        let diagnostics = error.diagnostics().expect("failure is located");
+        assert_eq!(diagnostics.offset, 16);
+        assert_eq!(diagnostics.path, "header.version");
+    }
+
+    #[test]
+    fn truncated_course_is_located() {
+        let mut bytes = skytrax_course().0;
+        // Drop the last connector entirely
+        bytes.truncate(bytes.len() - 12);
+
+        let error = SavedCourse::from_bytes(&bytes).expect_err("course is truncated");
+        let diagnostics = error.diagnostics().expect("failure is located");
+        assert_eq!(diagnostics.offset, bytes.len());
+        assert_eq!(diagnostics.path, "course.connectors[1].pos_x");
+        assert!(diagnostics.window.to_string().ends_with(">(end of file)\n"));
+    }
+
+    /// A count that was not read before is reported like any other failure.
+    #[test]
+    fn unknown_count_is_located() {
+        let bytes = [0; 4];
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let mut tracer = Tracer {
+            reader: Reader::new(&mut cursor),
+            path: vec!["course".to_string()],
+            scopes: Vec::new(),
+            version: None,
+        };
+        let structure = Struct {
+            module: module_path!(),
+            name: "Broken",
+            fields: vec![Field::List {
+                name: "values",
+                count: "value_count",
+                ty: i32::layout(),
+            }],
+        };
+
+        let failure = tracer.structure(&structure).expect_err("count is unknown");
+        assert_eq!(failure.offset, 0);
+        assert_eq!(failure.path, "course.value_count");
+    }
+
+    #[test]
+    fn hex_window_marks_the_offset() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let window = HexWindow::new(&bytes, 0x45);
+
+        assert_eq!(window.start, 0x20);
+        assert_eq!(window.bytes.len(), 0x65 - 0x20);
+        let dump = window.to_string();
+        assert!(dump.starts_with("00000020  20 21"));
+        assert!(dump.contains("00000040  40 41 42 43 44>45 46"));
+    }
+}
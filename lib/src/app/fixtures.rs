@@ -0,0 +1,406 @@
+//! Course files assembled by hand for the tests, byte by byte with [`RawCourse`] so they are
+//! independent of the parser and the `DekuWrite` implementation under test.
+use sha2::{Digest, Sha256};
+
+use crate::app::course::SavedCourse;
+use crate::app::layer::TileKind;
+use crate::app::scene::{Scene, Tile};
+
+/// Little-endian byte writer used to assemble course files by hand, independent of the
+/// `DekuWrite` implementation under test.
+#[derive(Default)]
+pub(crate) struct RawCourse(pub(crate) Vec<u8>);
+
+impl RawCourse {
+    pub(crate) fn i32(mut self, value: i32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn f32(mut self, value: f32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn u8(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    pub(crate) fn bytes(mut self, value: &[u8]) -> Self {
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    pub(crate) fn header(self, version: u32) -> Self {
+        self.bytes(&[0x42; 16]).u32(version)
+    }
+
+    pub(crate) fn meta_data(self, title: &str) -> Self {
+        self.bytes(&1_700_000_000_000u64.to_le_bytes())
+            .u8(title.len() as u8)
+            .bytes(title.as_bytes())
+            .i32(3) // order number
+            .u32(1) // course kind: Custom
+            .u32(0) // objective kind: None
+            .i32(2) // difficulty
+            .u8(1) // completed
+    }
+
+    pub(crate) fn with_checksum(self) -> Vec<u8> {
+        let checksum = Sha256::digest(&self.0);
+        self.bytes(&checksum).0
+    }
+}
+
+/// A SkyTrax course touching every optional field (retainer ids, power and light modes, rail
+/// exit heights, balconies with and without a cell, connectors).
+pub(crate) fn skytrax_course() -> RawCourse {
+    RawCourse::default()
+        .header(7)
+        .meta_data("Round trip")
+        .u32(13) // generation: SkyTrax
+        // one layer with one cell holding a light base with a starter on top
+        .i32(1)
+        .i32(0) // layer id
+        .u32(0) // base layer piece
+        .i32(-3) // position y
+        .i32(4) // position x
+        .i32(0) // small stacker height
+        .i32(1)
+        .i32(1) // cell local y
+        .i32(2) // cell local x
+        .i32(0) // index
+        .i32(1) // children
+        .u32(78) // light base
+        .i32(0)
+        .i32(2)
+        .i32(1024) // retainer id
+        .u32(2_147_483_648) // no power signal mode
+        .u32(4) // light stone colour: blue
+        .i32(1) // index
+        .i32(0) // children
+        .u32(1) // starter
+        .i32(3)
+        .i32(5)
+        .i32(-2_147_483_647) // no retainer id
+        .u32(1) // power signal: red
+        .u32(2_147_483_648) // no light stone colour
+        // one rail
+        .i32(1)
+        .i32(0)
+        .i32(1)
+        .i32(2)
+        .i32(3)
+        .f32(0.36)
+        .i32(0)
+        .i32(1)
+        .i32(5)
+        .i32(0)
+        .f32(-0.2)
+        .u32(0) // straight
+        // one pillar
+        .i32(1)
+        .i32(0)
+        .i32(1)
+        .i32(2)
+        .i32(1)
+        .i32(0)
+        .i32(0)
+        // one wall with two balconies
+        .i32(1)
+        .i32(0)
+        .i32(1)
+        .i32(1)
+        .i32(0)
+        .i32(4)
+        .i32(4)
+        .i32(2)
+        .i32(2000)
+        .u32(1) // east
+        .i32(1)
+        .i32(3)
+        .u8(1)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .u32(2) // curve
+        .i32(0)
+        .i32(4)
+        .i32(-2_147_483_647)
+        .u32(2_147_483_648)
+        .u32(2_147_483_648)
+        .i32(2001)
+        .u32(0) // west
+        .i32(2)
+        .i32(5)
+        .u8(0)
+        // two connectors
+        .i32(2)
+        .i32(1)
+        .i32(2)
+        .i32(3)
+        .i32(-1)
+        .i32(-2)
+        .i32(6)
+}
+
+/// A course of one of the formats before 2019 (see [`initiallaunch`](crate::app::initiallaunch)):
+/// one base layer with a starter in one cell and a curve on a small stacker in another, and
+/// one straight rail. The generation is only present from `PersistenceRefactor2019` on and so
+/// is the `materialized` flag of the rail.
+pub(crate) fn legacy_course(version: u32) -> Vec<u8> {
+    let refactored = version == 1;
+    let raw = RawCourse::default()
+        .header(version)
+        .meta_data("Tutorial")
+        .i32(1)
+        .i32(0)
+        .u32(0) // base layer piece
+        .f32(-0.36)
+        .i32(0)
+        .i32(0)
+        .i32(2)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(1)
+        .u32(1) // starter
+        .i32(3)
+        .i32(0)
+        .i32(3)
+        .i32(2)
+        .u32(16) // small stacker
+        .u32(2) // curve
+        // one straight rail spanning one empty field
+        .i32(1)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(2)
+        .i32(3)
+        .u32(0);
+    let raw = if refactored { raw.u8(1) } else { raw };
+    let raw = raw.i32(0); // no pillars
+    let raw = if refactored { raw.u32(1) } else { raw }; // generation: Christmas2018
+    raw.with_checksum()
+}
+
+/// A LightStones2023 course with a stacker tower on a base layer that carries a large layer
+/// via a pillar, a straight rail between both layers and a wall with a balcony.
+pub(crate) fn tower_course() -> Vec<u8> {
+    RawCourse::default()
+        .header(5)
+        .meta_data("Tower")
+        .u32(2) // layers
+        .i32(0) // layer id
+        .u32(0) // base layer piece
+        .f32(-0.36)
+        .i32(2) // position y
+        .i32(3) // position x
+        .i32(1) // cells
+        .i32(1) // cell local y
+        .i32(0) // cell local x
+        .i32(0) // index
+        .i32(1) // children
+        .u32(43) // stacker tower closed
+        .i32(2)
+        .i32(0)
+        .i32(100) // retainer id
+        .u32(2_147_483_648) // no power signal mode
+        .u32(2_147_483_648) // no light stone colour
+        .i32(1) // index
+        .i32(0) // children
+        .u32(2) // curve
+        .i32(1)
+        .i32(3)
+        .i32(-2_147_483_647) // no retainer id
+        .u32(2_147_483_648)
+        .u32(2_147_483_648)
+        .i32(1) // layer id
+        .u32(2) // large layer
+        .f32(6.84)
+        .i32(3)
+        .i32(5)
+        .i32(0) // cells
+        .u32(1) // rails
+        .i32(0)
+        .i32(1)
+        .i32(0)
+        .i32(0)
+        .f32(6.12) // on the curve, 17 small stackers up
+        .i32(1)
+        .i32(0)
+        .i32(0)
+        .i32(3)
+        .f32(0.0)
+        .u32(0) // straight
+        .u32(1) // pillars
+        .i32(100)
+        .i32(0)
+        .i32(0)
+        .i32(1)
+        .i32(0)
+        .i32(0)
+        .u32(9) // generation: Autumn2023
+        .i32(1) // walls
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(-3)
+        .i32(0)
+        .i32(1) // balconies
+        .i32(200)
+        .u32(1) // east
+        .i32(1)
+        .i32(0)
+        .u8(1)
+        .i32(0)
+        .i32(0)
+        .i32(0) // index
+        .i32(0) // children
+        .u32(1) // starter
+        .i32(0)
+        .i32(0)
+        .i32(-2_147_483_647)
+        .u32(2_147_483_648)
+        .u32(2_147_483_648)
+        .with_checksum()
+}
+
+/// A LightStones2023 course with a releaser carrying a starter, a vertical cannon and a space tube
+/// on a base layer, each with a pillar to a large layer at 19 small stackers.
+pub(crate) fn retainer_course() -> Vec<u8> {
+    let cell = |raw: RawCourse, (y, x): (i32, i32), kind: u32, height: i32, retainer_id: i32| {
+        raw.i32(y)
+            .i32(x)
+            .i32(0) // index
+            .i32(i32::from(retainer_id == 101)) // children: the starter on the releaser
+            .u32(kind)
+            .i32(height)
+            .i32(0)
+            .i32(retainer_id)
+            .u32(2_147_483_648) // no power signal mode
+            .u32(2_147_483_648) // no light stone colour
+    };
+    let pillar = |raw: RawCourse, lower_retainer_id: i32, upper_x: i32| {
+        raw.i32(lower_retainer_id)
+            .i32(0)
+            .i32(0)
+            .i32(1) // large layer
+            .i32(0)
+            .i32(upper_x)
+    };
+
+    let raw = RawCourse::default()
+        .header(5)
+        .meta_data("Retainers")
+        .u32(2) // layers
+        .i32(0) // layer id
+        .u32(0) // base layer piece
+        .f32(-0.36)
+        .i32(2) // position y
+        .i32(3) // position x
+        .i32(3); // cells
+    // The releaser stands right on the layer, the starter on it on a small stacker
+    let raw = cell(raw, (0, 0), 82, 0, 101) // releaser 1
+        .i32(1) // index
+        .i32(0) // children
+        .u32(1) // starter
+        .i32(1)
+        .i32(0)
+        .i32(-2_147_483_647) // no retainer id
+        .u32(2_147_483_648)
+        .u32(2_147_483_648);
+    let raw = cell(raw, (1, 0), 86, 2, 102); // vertical cannon 0 on a large stacker
+    let raw = cell(raw, (0, 1), 92, 1, 103) // space tube aligned on a small stacker
+        .i32(1) // layer id
+        .u32(2) // large layer
+        .f32(6.84)
+        .i32(3)
+        .i32(5)
+        .i32(0) // cells
+        .u32(0) // rails
+        .u32(3); // pillars
+    let raw = pillar(raw, 101, 0);
+    let raw = pillar(raw, 102, 1);
+    pillar(raw, 103, 2)
+        .u32(9) // generation: Autumn2023
+        .i32(0) // walls
+        .with_checksum()
+}
+
+/// A tile standing right on `base_height`, turned by 0 and without any modes set.
+pub(crate) fn tile(kind: TileKind, base_height: i32) -> Tile {
+    Tile {
+        kind,
+        hex_rotation: 0,
+        parent: None,
+        base_height,
+        height_in_small_stacker: Some(0),
+        on_light_base: false,
+        retainer_id: None,
+        power_signal_mode: None,
+        light_stone_color_mode: None,
+    }
+}
+
+pub(crate) fn tower_scene() -> Scene {
+    let course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+    Scene::try_from(&course).unwrap()
+}
+
+/// A ZiplineAdded2019 course (flat tile lists, `materialized` rails, ropes, no walls).
+pub(crate) fn zipline_course() -> RawCourse {
+    RawCourse::default()
+        .header(2)
+        .meta_data("Zipline")
+        // one layer with one cell holding two tiles
+        .i32(1)
+        .i32(0)
+        .u32(1) // base layer
+        .f32(-0.2)
+        .i32(0)
+        .i32(0)
+        .i32(1)
+        .i32(3) // hex rotation
+        .i32(1)
+        .i32(2)
+        .i32(2)
+        .u32(35) // zipline start
+        .u32(16) // small stacker
+        // one rail that is not materialized
+        .i32(1)
+        .i32(0)
+        .i32(0)
+        .i32(0)
+        .i32(1)
+        .i32(0)
+        .i32(2)
+        .i32(0)
+        .i32(4)
+        .u32(0)
+        .u8(0)
+        // no pillars
+        .i32(0)
+        // one rope
+        .i32(1)
+        .u32(0)
+        .i32(1)
+        .i32(2)
+        .u32(0)
+        .i32(5)
+        .i32(5)
+        .u32(1)
+        .u32(3) // generation: Autumn2019
+}
@@ -0,0 +1,793 @@
+//! Exports a course as a 3D model in the [glTF](https://www.khronos.org/gltf/) format, which most
+//! 3D viewers and Blender can open.
+//!
+//! Pieces are simple shapes placed at their [`Poses`]: tiles, stackers, layers, balconies and
+//! connectors are hexagonal prisms, rails and walls are boxes. Every [`TileKind`], [`RailKind`],
+//! [`LayerKind`] and [`WallKind`] gets a mesh of its own (named after it) so they can be told
+//! apart and replaced with proper models. Tiles are prisms whatever their shape, so a curve looks
+//! like any other tile. Rails that bend (see [`catalogue::RailSpec::turn`]) are drawn as a chain
+//! of bars leaving each exit in the direction it faces, the others as a straight bar between
+//! their exits. Layers are drawn as the cells they carry, their outline is not known.
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Value, json};
+
+use crate::app::course::{Course, HexVector};
+use crate::app::layer::{LayerKind, TileKind};
+use crate::app::pose::{CELL_DISTANCE_MM, CELL_RADIUS_MM, Pose, Poses, SMALL_STACKER_HEIGHT_MM};
+use crate::app::rail::RailKind;
+use crate::app::scene::{self, Scene};
+use crate::app::wall::WallKind;
+use crate::catalogue;
+
+/// Tiles are drawn a little smaller than their cells, so neighbors can be told apart.
+const TILE_SCALE: f32 = 0.95;
+const RAIL_WIDTH_MM: f32 = 8.0;
+const RAIL_THICKNESS_MM: f32 = 4.0;
+const WALL_THICKNESS_MM: f32 = 6.0;
+const CONNECTOR_RADIUS_MM: f32 = 5.0;
+/// How many bars a rail that bends is drawn with.
+const RAIL_SEGMENTS: usize = 8;
+
+/// glTF component types and buffer view targets.
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// A course as a glTF document, with all geometry in a single binary buffer.
+#[derive(Debug)]
+pub struct Model {
+    /// The glTF JSON, without the `buffers` which depend on how the model is written.
+    document: Value,
+    buffer: Vec<u8>,
+}
+
+impl TryFrom<&Course> for Model {
+    type Error = scene::Error;
+
+    fn try_from(course: &Course) -> Result<Self, scene::Error> {
+        Ok(Model::from(&Scene::try_from(course)?))
+    }
+}
+
+impl From<&Scene> for Model {
+    fn from(scene: &Scene) -> Self {
+        ModelBuilder::new().build(scene, &Poses::from(scene))
+    }
+}
+
+impl Model {
+    /// The model as a binary glTF (`.glb`) file.
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut document = self.document.clone();
+        document["buffers"] = json!([{ "byteLength": self.buffer.len() }]);
+        let mut json = serde_json::to_vec(&document).expect("glTF JSON can be serialized");
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut buffer = self.buffer.clone();
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+        let length = 12 + 8 + json.len() + 8 + buffer.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&buffer);
+        glb
+    }
+
+    /// The model as a glTF (`.gltf`) document with the buffer embedded as a data URI.
+    pub fn to_gltf(&self) -> Value {
+        let mut document = self.document.clone();
+        document["buffers"] = json!([{
+            "byteLength": self.buffer.len(),
+            "uri": format!("data:application/octet-stream;base64,{}", STANDARD.encode(&self.buffer)),
+        }]);
+        document
+    }
+}
+
+/// What a mesh is used for. Meshes are named after kinds and kinds of different things can have
+/// the same name, e.g. the tile `LightStacker` and a column of lit stackers.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Category {
+    Layer,
+    Stacker,
+    Tile,
+    Rail,
+    Wall,
+    Balcony,
+    Connector,
+}
+
+/// The two shapes everything is made of, by their accessors.
+#[derive(Clone, Copy)]
+struct Geometry {
+    positions: usize,
+    indices: usize,
+}
+
+struct ModelBuilder {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    mesh_indices: HashMap<(Category, String), usize>,
+    nodes: Vec<Value>,
+    /// A hexagonal prism with a radius (center to corner) of 1, from height 0 to 1.
+    prism: Geometry,
+    /// A cube with sides of 1 around the origin.
+    cube: Geometry,
+}
+
+impl ModelBuilder {
+    fn new() -> ModelBuilder {
+        let mut builder = ModelBuilder {
+            buffer: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+            materials: Vec::new(),
+            meshes: Vec::new(),
+            mesh_indices: HashMap::new(),
+            nodes: Vec::new(),
+            prism: Geometry {
+                positions: 0,
+                indices: 0,
+            },
+            cube: Geometry {
+                positions: 0,
+                indices: 0,
+            },
+        };
+
+        // Corners at 30°, 90°, ... so the flat sides face the neighbors east and west
+        let mut prism = Vec::new();
+        for y in [0.0, 1.0] {
+            for corner in 0..6 {
+                let (sin, cos) = (30.0f32 + 60.0 * corner as f32).to_radians().sin_cos();
+                prism.push(gltf_vector(cos, sin, y));
+            }
+        }
+        prism.push([0.0, 0.0, 0.0]);
+        prism.push([0.0, 1.0, 0.0]);
+        let mut triangles = Vec::new();
+        for corner in 0..6 {
+            let next = (corner + 1) % 6;
+            triangles.push([12, corner, next]);
+            triangles.push([13, 6 + corner, 6 + next]);
+            triangles.push([corner, next, 6 + next]);
+            triangles.push([corner, 6 + next, 6 + corner]);
+        }
+        builder.prism = builder.add_geometry(&prism, triangles);
+
+        let cube: Vec<_> = (0..8)
+            .map(|corner| {
+                let side = |bit: u16| if corner & bit == 0 { -0.5 } else { 0.5 };
+                [side(1), side(2), side(4)]
+            })
+            .collect();
+        let mut triangles = Vec::new();
+        // Every side is two triangles between the corners with one coordinate in common
+        for (bit, [first, second]) in [(1, [2, 4]), (2, [1, 4]), (4, [1, 2])] {
+            for base in [0, bit] {
+                triangles.push([base, base | first, base | first | second]);
+                triangles.push([base, base | first | second, base | second]);
+            }
+        }
+        builder.cube = builder.add_geometry(&cube, triangles);
+
+        builder
+    }
+
+    /// Stores a shape, turning its triangles so they face away from its center.
+    fn add_geometry(&mut self, positions: &[[f32; 3]], triangles: Vec<[u16; 3]>) -> Geometry {
+        let count = positions.len() as f32;
+        let center: [f32; 3] =
+            std::array::from_fn(|axis| positions.iter().map(|p| p[axis]).sum::<f32>() / count);
+        let triangles: Vec<[u16; 3]> = triangles
+            .into_iter()
+            .map(|[a, b, c]| {
+                let [pa, pb, pc] = [a, b, c].map(|index| positions[usize::from(index)]);
+                let normal = cross(subtract(pb, pa), subtract(pc, pa));
+                let outwards = subtract(pa, center);
+                if dot(normal, outwards) < 0.0 {
+                    [a, c, b]
+                } else {
+                    [a, b, c]
+                }
+            })
+            .collect();
+
+        let min: [f32; 3] =
+            std::array::from_fn(|axis| positions.iter().map(|p| p[axis]).fold(f32::MAX, f32::min));
+        let max: [f32; 3] =
+            std::array::from_fn(|axis| positions.iter().map(|p| p[axis]).fold(f32::MIN, f32::max));
+        let bytes: Vec<u8> = positions
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let view = self.add_buffer_view(&bytes, ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": positions.len(),
+            "type": "VEC3",
+            "min": min,
+            "max": max,
+        }));
+        let positions = self.accessors.len() - 1;
+
+        let bytes: Vec<u8> = triangles
+            .iter()
+            .flatten()
+            .flat_map(|index| index.to_le_bytes())
+            .collect();
+        let view = self.add_buffer_view(&bytes, ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_SHORT,
+            "count": triangles.len() * 3,
+            "type": "SCALAR",
+        }));
+        let indices = self.accessors.len() - 1;
+
+        Geometry { positions, indices }
+    }
+
+    fn add_buffer_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        // Every view starts aligned to 4 bytes so floats can be read directly
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.len() - 1
+    }
+
+    /// The mesh called `name` for a `category` of pieces, created from `geometry` in `color` the
+    /// first time it is used.
+    fn mesh(
+        &mut self,
+        category: Category,
+        name: String,
+        geometry: Geometry,
+        color: [f32; 4],
+    ) -> usize {
+        let key = (category, name);
+        if let Some(&mesh) = self.mesh_indices.get(&key) {
+            return mesh;
+        }
+        let name = &key.1;
+        let mut material = json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": color,
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.8,
+            },
+        });
+        if color[3] < 1.0 {
+            material["alphaMode"] = json!("BLEND");
+        }
+        self.materials.push(material);
+        self.meshes.push(json!({
+            "name": name,
+            "primitives": [{
+                "attributes": { "POSITION": geometry.positions },
+                "indices": geometry.indices,
+                "material": self.materials.len() - 1,
+            }],
+        }));
+        self.mesh_indices.insert(key, self.meshes.len() - 1);
+        self.meshes.len() - 1
+    }
+
+    /// Adds a node, `scale` is in millimetres along the piece (x), up (z) and across it (y).
+    fn node(&mut self, name: String, mesh: usize, pose: Pose, scale: [f32; 3]) -> usize {
+        let (sin, cos) = (pose.yaw.to_radians() / 2.0).sin_cos();
+        self.nodes.push(json!({
+            "name": name,
+            "mesh": mesh,
+            "translation": gltf_point(pose.x, pose.y, pose.z),
+            "rotation": [0.0, sin, 0.0, cos],
+            "scale": [scale[0] / 1000.0, scale[1] / 1000.0, scale[2] / 1000.0],
+        }));
+        self.nodes.len() - 1
+    }
+
+    /// A hexagonal prism on a cell, `radius` is relative to the cell.
+    fn prism_node(
+        &mut self,
+        name: String,
+        mesh: usize,
+        pose: Pose,
+        radius: f32,
+        height: f32,
+    ) -> usize {
+        let radius = radius * CELL_RADIUS_MM;
+        self.node(name, mesh, pose, [radius, height, radius])
+    }
+
+    /// A box from `from` to `to`, the middle of its bottom on the line between them.
+    fn bar_node(
+        &mut self,
+        name: String,
+        mesh: usize,
+        from: [f32; 3],
+        to: [f32; 3],
+        [width, height]: [f32; 2],
+    ) -> usize {
+        let direction = subtract(to, from);
+        let length = dot(direction, direction).sqrt();
+        // The rotation turning the x axis of the box along the bar
+        let rotation = if length == 0.0 {
+            [0.0, 0.0, 0.0, 1.0]
+        } else {
+            let [x, y, z] = direction.map(|value| value / length);
+            if x < -0.999_999 {
+                [0.0, 1.0, 0.0, 0.0]
+            } else {
+                let [qx, qy, qz, qw] = [0.0, -z, y, 1.0 + x];
+                let norm = (qx * qx + qy * qy + qz * qz + qw * qw).sqrt();
+                [qx / norm, qy / norm, qz / norm, qw / norm]
+            }
+        };
+        let middle: [f32; 3] = std::array::from_fn(|axis| (from[axis] + to[axis]) / 2.0);
+        // The box is centered around the origin, lift it onto the line
+        let lift = height / 2000.0;
+        self.nodes.push(json!({
+            "name": name,
+            "mesh": mesh,
+            "translation": [middle[0], middle[1] + lift, middle[2]],
+            "rotation": rotation,
+            "scale": [length, height / 1000.0, width / 1000.0],
+        }));
+        self.nodes.len() - 1
+    }
+
+    fn build(mut self, scene: &Scene, poses: &Poses) -> Model {
+        let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+
+        let mut layers = Vec::new();
+        for layer in &poses.layers {
+            let mesh = self.mesh(
+                Category::Layer,
+                format!("{:?}", layer.kind),
+                self.prism,
+                layer_color(&layer.kind),
+            );
+            let cells: BTreeSet<(i32, i32)> = scene
+                .cells
+                .iter()
+                .filter(|cell| cell.retainer_id == layer.id)
+                .map(|cell| (cell.position.x, cell.position.y))
+                .chain(
+                    scene
+                        .layers
+                        .iter()
+                        .find(|l| l.id == layer.id)
+                        .map(|l| (l.position.x, l.position.y)),
+                )
+                .collect();
+            for (x, y) in cells {
+                let pose = Pose {
+                    z: layer.pose.z,
+                    ..Pose::at(&HexVector::new(x, y), 0)
+                };
+                let name = format!("Layer {} ({x}, {y})", layer.id);
+                layers.push(self.prism_node(name, mesh, pose, 1.0, layer.height.max(1.0)));
+            }
+        }
+        groups.push(("Layers", layers));
+
+        let mut stackers = Vec::new();
+        for column in &poses.columns {
+            let name = if column.lit {
+                "LightStacker"
+            } else {
+                "Stacker"
+            };
+            let mesh = self.mesh(
+                Category::Stacker,
+                name.to_string(),
+                self.prism,
+                stacker_color(column.lit),
+            );
+            let name = format!("{name} {:?}", column.origin);
+            stackers.push(self.prism_node(name, mesh, column.pose, 0.9, column.height));
+        }
+        groups.push(("Stackers", stackers));
+
+        let mut tiles = Vec::new();
+        for tile in &poses.tiles {
+            let name = format!("{:?}", tile.kind);
+            let mesh = self.mesh(
+                Category::Tile,
+                name.clone(),
+                self.prism,
+                tile_color(&tile.kind),
+            );
+            // Some pieces (e.g. stacker batches) have no known height, keep them visible
+            let height = tile.height.max(SMALL_STACKER_HEIGHT_MM / 4.0);
+            tiles.push(self.prism_node(name, mesh, tile.pose, TILE_SCALE, height));
+        }
+        groups.push(("Tiles", tiles));
+
+        let mut rails = Vec::new();
+        for rail in &poses.rails {
+            let mesh = self.mesh(
+                Category::Rail,
+                format!("{:?}", rail.kind),
+                self.cube,
+                rail_color(&rail.kind),
+            );
+            let name = format!("Rail [{}] {:?}", rail.index, rail.kind);
+            let size = [RAIL_WIDTH_MM, RAIL_THICKNESS_MM];
+            let path = rail_path(rail.exits, catalogue::rail(&rail.kind).turn);
+            if let [from, to] = path[..] {
+                rails.push(self.bar_node(name, mesh, from, to, size));
+                continue;
+            }
+            let segments: Vec<_> = path
+                .windows(2)
+                .enumerate()
+                .map(|(index, bar)| {
+                    let name = format!("{name} part {}", index + 1);
+                    self.bar_node(name, mesh, bar[0], bar[1], size)
+                })
+                .collect();
+            self.nodes
+                .push(json!({ "name": name, "children": segments }));
+            rails.push(self.nodes.len() - 1);
+        }
+        groups.push(("Rails", rails));
+
+        let mut walls = Vec::new();
+        let mut balconies = Vec::new();
+        for (wall, pose) in scene.walls.iter().zip(&poses.walls) {
+            let name = match WallKind::straight_of_length(wall.length()) {
+                Some(kind) => format!("{kind:?}"),
+                None => "Wall".to_string(),
+            };
+            let mesh = self.mesh(
+                Category::Wall,
+                name.clone(),
+                self.cube,
+                [0.9, 0.85, 0.7, 1.0],
+            );
+            let height = small_stackers(wall.rows() * 2);
+            let end = pose.pose.forward(pose.length);
+            walls.push(self.bar_node(
+                format!("Wall [{}] {name}", wall.index),
+                mesh,
+                gltf_point(pose.pose.x, pose.pose.y, pose.pose.z),
+                gltf_point(end.x, end.y, end.z),
+                [WALL_THICKNESS_MM, height],
+            ));
+        }
+        for balcony in &poses.balconies {
+            let mesh = self.mesh(
+                Category::Balcony,
+                "Balcony".to_string(),
+                self.prism,
+                [0.9, 0.85, 0.7, 1.0],
+            );
+            let name = format!("Balcony {} on wall [{}]", balcony.retainer_id, balcony.wall);
+            balconies.push(self.prism_node(
+                name,
+                mesh,
+                balcony.pose,
+                TILE_SCALE,
+                small_stackers(1),
+            ));
+        }
+        groups.push(("Walls", walls));
+        groups.push(("Balconies", balconies));
+
+        let mut connectors = Vec::new();
+        for connector in &poses.connectors {
+            let mesh = self.mesh(
+                Category::Connector,
+                "Connector".to_string(),
+                self.prism,
+                [0.2, 0.2, 0.2, 1.0],
+            );
+            let radius = CONNECTOR_RADIUS_MM / CELL_RADIUS_MM;
+            let name = format!("Connector [{}]", connector.index);
+            connectors.push(self.prism_node(name, mesh, connector.pose, radius, small_stackers(1)));
+        }
+        groups.push(("Connectors", connectors));
+
+        let mut children = Vec::new();
+        for (name, group) in groups {
+            self.nodes.push(json!({ "name": name, "children": group }));
+            children.push(self.nodes.len() - 1);
+        }
+        self.nodes
+            .push(json!({ "name": "Course", "children": children }));
+        let root = self.nodes.len() - 1;
+
+        let document = json!({
+            "asset": { "version": "2.0", "generator": "murmelbahn" },
+            "scene": 0,
+            "scenes": [{ "nodes": [root] }],
+            "nodes": self.nodes,
+            "meshes": self.meshes,
+            "materials": self.materials,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+        });
+        Model {
+            document,
+            buffer: self.buffer,
+        }
+    }
+}
+
+fn small_stackers(height: i32) -> f32 {
+    height as f32 * SMALL_STACKER_HEIGHT_MM
+}
+
+/// The points (in glTF coordinates) a rail runs through from one exit to the other. A rail that
+/// bends leaves each exit in the direction the exit faces and is drawn along a Bézier curve.
+fn rail_path(exits: [Pose; 2], turn: u8) -> Vec<[f32; 3]> {
+    let [from, to] = exits.map(|exit| [exit.x, exit.y, exit.z]);
+    if turn == 0 {
+        return [from, to].map(|[x, y, z]| gltf_point(x, y, z)).to_vec();
+    }
+    let chord = subtract(to, from);
+    // Far enough out that exits facing the same way (a U-turn) still get a round bend
+    let reach = (dot(chord, chord).sqrt() / 2.0).max(CELL_DISTANCE_MM / 2.0);
+    let [towards_from, towards_to] = exits.map(|exit| {
+        let ahead = exit.forward(reach);
+        [ahead.x, ahead.y, exit.z]
+    });
+    (0..=RAIL_SEGMENTS)
+        .map(|step| {
+            let t = step as f32 / RAIL_SEGMENTS as f32;
+            let weights = [
+                (1.0 - t).powi(3),
+                3.0 * (1.0 - t).powi(2) * t,
+                3.0 * (1.0 - t) * t * t,
+                t.powi(3),
+            ];
+            let [x, y, z] = std::array::from_fn(|axis| {
+                [from, towards_from, towards_to, to]
+                    .iter()
+                    .zip(weights)
+                    .map(|(point, weight)| point[axis] * weight)
+                    .sum()
+            });
+            gltf_point(x, y, z)
+        })
+        .collect()
+}
+
+/// glTF has y pointing up and uses metres.
+fn gltf_point(x: f32, y: f32, z: f32) -> [f32; 3] {
+    gltf_vector(x / 1000.0, y / 1000.0, z / 1000.0)
+}
+
+fn gltf_vector(x: f32, y: f32, z: f32) -> [f32; 3] {
+    [x, z, -y]
+}
+
+fn subtract(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn layer_color(kind: &LayerKind) -> [f32; 4] {
+    match kind {
+        LayerKind::BaseLayerPiece | LayerKind::BaseLayer => [0.3, 0.3, 0.3, 1.0],
+        // The clear layers are see-through
+        _ => [0.7, 0.85, 1.0, 0.4],
+    }
+}
+
+fn stacker_color(lit: bool) -> [f32; 4] {
+    if lit {
+        [1.0, 1.0, 0.8, 1.0]
+    } else {
+        [0.75, 0.75, 0.75, 1.0]
+    }
+}
+
+fn tile_color(kind: &TileKind) -> [f32; 4] {
+    if kind.is_stacker() {
+        return stacker_color(false);
+    }
+    if catalogue::tile(kind).retainer.is_some() {
+        return [0.5, 0.5, 0.5, 1.0];
+    }
+    // Every other tile gets a color of its own, the same in every model
+    let hue = format!("{kind:?}")
+        .bytes()
+        .fold(2_166_136_261u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(16_777_619)
+        }) as f32
+        / u32::MAX as f32;
+    hue_color(hue)
+}
+
+fn rail_color(kind: &RailKind) -> [f32; 4] {
+    if catalogue::rail(kind).straight {
+        [0.85, 0.85, 0.9, 1.0]
+    } else {
+        [0.6, 0.7, 0.9, 1.0]
+    }
+}
+
+/// A saturated color with the given hue (`0..1`).
+fn hue_color(hue: f32) -> [f32; 4] {
+    let channel = |offset: f32| {
+        let distance = ((hue + offset).fract() * 6.0 - 3.0).abs();
+        (distance - 1.0).clamp(0.0, 1.0) * 0.7 + 0.2
+    };
+    [channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0), 1.0]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{Category, Model, ModelBuilder, RAIL_SEGMENTS, rail_path};
+    use crate::app::fixtures::tower_scene;
+    use crate::app::pose::{Pose, SMALL_STACKER_HEIGHT_MM};
+    use crate::app::rail::RailKind;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Splits a GLB file into its JSON and binary chunk.
+    fn read_glb(glb: &[u8]) -> (Value, &[u8]) {
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32_at(glb, 4), 2);
+        assert_eq!(u32_at(glb, 8) as usize, glb.len());
+        let json_length = u32_at(glb, 12) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        let json = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        let bin = &glb[20 + json_length..];
+        assert_eq!(&bin[4..8], b"BIN\0");
+        let bin_length = u32_at(bin, 0) as usize;
+        assert_eq!(bin.len(), 8 + bin_length);
+        (json, &bin[8..])
+    }
+
+    #[test]
+    fn glb_files_are_self_contained() {
+        let glb = Model::from(&tower_scene()).to_glb();
+        let (document, buffer) = read_glb(&glb);
+
+        assert!(document["buffers"][0]["byteLength"].as_u64().unwrap() <= buffer.len() as u64);
+        for view in document["bufferViews"].as_array().unwrap() {
+            let end = view["byteOffset"].as_u64().unwrap() + view["byteLength"].as_u64().unwrap();
+            assert!(end <= buffer.len() as u64);
+        }
+        let meshes = document["meshes"].as_array().unwrap();
+        let nodes = document["nodes"].as_array().unwrap();
+        for node in nodes {
+            if let Some(mesh) = node["mesh"].as_u64() {
+                assert!((mesh as usize) < meshes.len());
+            }
+            for child in node["children"].as_array().into_iter().flatten() {
+                assert!((child.as_u64().unwrap() as usize) < nodes.len());
+            }
+        }
+        let names: Vec<_> = meshes
+            .iter()
+            .map(|mesh| mesh["name"].as_str().unwrap())
+            .collect();
+        for name in [
+            "StackerTowerClosed",
+            "Curve",
+            "Starter",
+            "Straight",
+            "StraightMedium",
+        ] {
+            assert!(names.contains(&name), "{name} in {names:?}");
+        }
+    }
+
+    /// glTF has y pointing up, z pointing south and uses metres.
+    #[test]
+    fn pieces_are_placed_at_their_pose() {
+        let scene = tower_scene();
+        let document = Model::from(&scene).to_gltf();
+        assert!(
+            document["buffers"][0]["uri"]
+                .as_str()
+                .unwrap()
+                .starts_with("data:application/octet-stream;base64,")
+        );
+
+        let curve = document["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node| node["name"] == "Curve")
+            .unwrap();
+        let pose = Pose::at(&scene.cells[0].position, 17);
+        let translation: Vec<f64> = curve["translation"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_f64().unwrap())
+            .collect();
+        let expected = [pose.x / 1000.0, pose.z / 1000.0, -pose.y / 1000.0];
+        for (actual, expected) in translation.iter().zip(expected) {
+            assert!((actual - f64::from(expected)).abs() < 1e-6);
+        }
+        let height = curve["scale"][1].as_f64().unwrap();
+        assert!((height - f64::from(SMALL_STACKER_HEIGHT_MM) / 1000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn meshes_of_the_same_name_are_kept_apart() {
+        let mut builder = ModelBuilder::new();
+        let prism = builder.prism;
+        let tile = builder.mesh(Category::Tile, "LightStacker".into(), prism, [1.0; 4]);
+        let column = builder.mesh(Category::Stacker, "LightStacker".into(), prism, [0.5; 4]);
+        assert_ne!(tile, column);
+        assert_eq!(
+            builder.mesh(Category::Tile, "LightStacker".into(), prism, [1.0; 4]),
+            tile
+        );
+    }
+
+    #[test]
+    fn bent_rails_leave_their_exits_in_the_direction_they_face() {
+        // Both exits of a U-turn face east, the rail bulges out to the east between them
+        let exits = [
+            Pose::default(),
+            Pose {
+                y: 50.0,
+                ..Pose::default()
+            },
+        ];
+        let path = rail_path(exits, 3);
+        assert_eq!(path.len(), RAIL_SEGMENTS + 1);
+        assert_eq!(path[0], [0.0, 0.0, 0.0]);
+        assert!((path[RAIL_SEGMENTS][2] + 0.05).abs() < 1e-6);
+        assert!(path[RAIL_SEGMENTS / 2][0] > 0.01);
+
+        // A rail that doesn't bend is a single bar
+        assert_eq!(rail_path(exits, 0).len(), 2);
+
+        let mut scene = tower_scene();
+        scene.rails[0].kind = RailKind::FlexTube60;
+        let document = Model::from(&scene).to_gltf();
+        let rail = document["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node| node["name"] == "Rail [0] FlexTube60")
+            .unwrap();
+        assert_eq!(rail["children"].as_array().unwrap().len(), RAIL_SEGMENTS);
+    }
+}
@@ -0,0 +1,299 @@
+//! How the tiles of a course are connected, either by rails or by sitting right next to each other.
+//!
+//! The graph is built from a [`Scene`] so it works the same for every format.
+//! Edges point the way marbles run: from a side of a tile marbles leave through to a side of a
+//! tile they enter through, according to the openings in the [`catalogue`]. Sides marbles run
+//! through in both directions give an edge each way. Two tiles next to each other at the same
+//! height are connected through their shared side, rails through the sides their exits are on.
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use serde::Serialize;
+
+use crate::app::course::{Direction, HexVector};
+use crate::app::layer::TileKind;
+use crate::app::scene::{RailExit, Scene};
+use crate::catalogue::{self, OpeningKind};
+
+#[derive(Debug, Default, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A tile a marble can run through.
+#[derive(Debug, Serialize)]
+pub struct Node {
+    /// The index of the cell in [`Scene::cells`].
+    pub cell: usize,
+    /// The index of the tile in [`Cell::tiles`](crate::app::scene::Cell::tiles).
+    pub tile: usize,
+    pub kind: TileKind,
+    pub hex_rotation: i32,
+    pub position: HexVector,
+    /// The height the tile sits at, in small stackers.
+    pub height: i32,
+}
+
+/// A way a marble can run from one node to another.
+#[derive(Debug, Serialize)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// The index of the node in [`Graph::nodes`].
+    pub from: usize,
+    pub to: usize,
+    /// The side of the `from` tile the marble leaves through.
+    pub from_side: Direction,
+    /// The side of the `to` tile the marble enters through.
+    pub to_side: Direction,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum EdgeKind {
+    /// A rail, by its index in the course file.
+    Rail(usize),
+    /// Two tiles at the same height in neighboring cells, one open towards the other for marbles
+    /// to leave and the other one for marbles to enter.
+    Adjacent,
+}
+
+impl From<&Scene> for Graph {
+    fn from(scene: &Scene) -> Self {
+        let mut graph = Graph::default();
+
+        // Stackers only lift tiles and most retainers (e.g. stacker towers) only carry them
+        for (cell_index, cell) in scene.cells.iter().enumerate() {
+            for (tile_index, tile) in cell.tiles.iter().enumerate() {
+                if catalogue::tile(&tile.kind).is_closed() {
+                    continue;
+                }
+                graph.nodes.push(Node {
+                    cell: cell_index,
+                    tile: tile_index,
+                    kind: tile.kind.clone(),
+                    hex_rotation: tile.hex_rotation,
+                    position: cell.position.clone(),
+                    height: tile.height(),
+                });
+            }
+        }
+
+        let mut nodes_by_position: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (index, node) in graph.nodes.iter().enumerate() {
+            nodes_by_position
+                .entry((node.position.y, node.position.x))
+                .or_default()
+                .push(index);
+        }
+
+        for rail in &scene.rails {
+            if !rail.materialized {
+                continue;
+            }
+            let exits = [&rail.exit_1, &rail.exit_2].map(|exit| {
+                let node = graph.rail_exit_node(scene, exit)?;
+                let side = Direction::hex_rotation_to_direction(exit.side_hex_rotation)?;
+                Some((node, side, graph.opening(node, side)?))
+            });
+            let [Some(first), Some(second)] = exits else {
+                continue;
+            };
+            for ((from, from_side, leaves), (to, to_side, enters)) in
+                [(first, second), (second, first)]
+            {
+                if leaves.exits() && enters.enters() {
+                    graph.edges.push(Edge {
+                        kind: EdgeKind::Rail(rail.index),
+                        from,
+                        to,
+                        from_side,
+                        to_side,
+                    });
+                }
+            }
+        }
+
+        let mut adjacent = Vec::new();
+        for from in 0..graph.nodes.len() {
+            let from_node = &graph.nodes[from];
+            for direction in Direction::ALL {
+                if !graph
+                    .opening(from, direction)
+                    .is_some_and(OpeningKind::exits)
+                {
+                    continue;
+                }
+                let neighbor = from_node.position.neighbor(&direction);
+                let Some(candidates) = nodes_by_position.get(&(neighbor.y, neighbor.x)) else {
+                    continue;
+                };
+                for &to in candidates {
+                    if from_node.height == graph.nodes[to].height
+                        && graph
+                            .opening(to, direction.opposite())
+                            .is_some_and(OpeningKind::enters)
+                    {
+                        adjacent.push(Edge {
+                            kind: EdgeKind::Adjacent,
+                            from,
+                            to,
+                            from_side: direction,
+                            to_side: direction.opposite(),
+                        });
+                    }
+                }
+            }
+        }
+        graph.edges.extend(adjacent);
+
+        graph
+    }
+}
+
+impl Graph {
+    /// How marbles run through `side` of a node, `None` if the side is closed.
+    fn opening(&self, node: usize, side: Direction) -> Option<OpeningKind> {
+        let node = &self.nodes[node];
+        catalogue::tile(&node.kind).opening(side, node.hex_rotation)
+    }
+
+    /// The nodes a marble can run to from `node`.
+    pub fn successors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.from == node)
+            .map(|edge| edge.to)
+    }
+
+    /// The nodes marbles are started from (e.g. a [`TileKind::Starter`]).
+    pub fn starters(&self) -> impl Iterator<Item = usize> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.kind.is_starter())
+            .map(|(index, _)| index)
+    }
+
+    /// All nodes a marble starting at `start` can reach, including `start` itself.
+    pub fn reachable_from(&self, start: usize) -> BTreeSet<usize> {
+        let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for edge in &self.edges {
+            successors.entry(edge.from).or_default().push(edge.to);
+        }
+
+        let mut reached = BTreeSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for &next in successors.get(&node).into_iter().flatten() {
+                if reached.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// The nodes reachable from every starter, keyed by the node of the starter.
+    pub fn reachable_from_starters(&self) -> BTreeMap<usize, BTreeSet<usize>> {
+        self.starters()
+            .map(|starter| (starter, self.reachable_from(starter)))
+            .collect()
+    }
+
+    /// Finds the node a rail exit is attached to, see [`Scene::rail_exit_tile`].
+    pub fn rail_exit_node(&self, scene: &Scene, exit: &RailExit) -> Option<usize> {
+        let tile = scene.rail_exit_tile(exit)?;
+        self.nodes
+            .iter()
+            .position(|node| node.cell == tile.cell && node.tile == tile.tile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EdgeKind, Graph};
+    use crate::app::course::Direction;
+    use crate::app::fixtures::{tile, tower_scene};
+    use crate::app::layer::TileKind;
+    use crate::app::scene::Cell;
+
+    #[test]
+    fn tiles_without_neighbors_are_not_connected() {
+        let scene = tower_scene();
+        let graph = Graph::from(&scene);
+
+        // The stacker tower only carries the curve
+        let kinds: Vec<_> = graph.nodes.iter().map(|node| node.kind.clone()).collect();
+        assert_eq!(kinds, [TileKind::Curve, TileKind::Starter]);
+
+        // The rail starts at the curve but there is no tile on the large layer it ends on, and
+        // the starter on the balcony is next to nothing at its height
+        assert!(graph.edges.is_empty());
+    }
+
+    #[test]
+    fn starters_reach_connected_tiles() {
+        let mut scene = tower_scene();
+        // Move the balcony's starter right next to the curve, at the same height
+        let position = scene.cells[0].position.neighbor(&Direction::West);
+        let starter_cell = &mut scene.cells[1];
+        starter_cell.position = position;
+        starter_cell.tiles[0].base_height = 16;
+        starter_cell.tiles[0].height_in_small_stacker = Some(1);
+
+        // Marbles only run from the starter to the curve, not back
+        let graph = Graph::from(&scene);
+        assert_eq!(graph.edges.len(), 1);
+        let edge = &graph.edges[0];
+        assert_eq!(edge.kind, EdgeKind::Adjacent);
+        assert_eq!((edge.from, edge.to), (1, 0));
+        assert_eq!(edge.from_side, Direction::East);
+        assert_eq!(edge.to_side, Direction::West);
+        assert_eq!(graph.successors(0).count(), 0);
+
+        let reachable = graph.reachable_from_starters();
+        assert_eq!(reachable.len(), 1);
+        assert_eq!(reachable[&1].iter().copied().collect::<Vec<_>>(), [0, 1]);
+
+        // A starter facing away from the curve is not connected to it
+        scene.cells[1].tiles[0].hex_rotation = 3;
+        assert!(Graph::from(&scene).edges.is_empty());
+    }
+
+    #[test]
+    fn rails_run_from_exits_to_entries_at_their_height() {
+        let mut scene = tower_scene();
+        // Put a goal basin on the large layer where the rail ends and a curve above it
+        let end = scene.rails[0].exit_2.position.clone();
+        scene.cells.push(Cell {
+            retainer_id: 1,
+            position: end,
+            tiles: vec![tile(TileKind::GoalBasin, 20), tile(TileKind::Curve, 24)],
+        });
+        // The east side of the curve on the tower is closed at rotation 3, the west side is not
+        scene.rails[0].exit_1.side_hex_rotation = 3;
+
+        // The exit on the large layer is at its height, on the goal basin rather than the curve
+        // above it, and marbles can't run out of the basin
+        let graph = Graph::from(&scene);
+        assert_eq!(graph.edges.len(), 1);
+        let edge = &graph.edges[0];
+        assert_eq!(edge.kind, EdgeKind::Rail(0));
+        assert_eq!(graph.nodes[edge.from].kind, TileKind::Curve);
+        assert_eq!(graph.nodes[edge.from].height, 17);
+        assert_eq!(graph.nodes[edge.to].kind, TileKind::GoalBasin);
+        assert_eq!(edge.from_side, Direction::West);
+        assert_eq!(edge.to_side, Direction::West);
+
+        // Exits before 2020 have no height and are on the highest tile, marbles run both ways
+        // between two curves
+        scene.rails[0].exit_2.local_height = None;
+        scene.cells[2].tiles[1].hex_rotation = 3;
+        let graph = Graph::from(&scene);
+        let heights: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (graph.nodes[edge.from].height, graph.nodes[edge.to].height))
+            .collect();
+        assert_eq!(heights, [(17, 24), (24, 17)]);
+    }
+}
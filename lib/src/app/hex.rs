@@ -0,0 +1,305 @@
+//! Geometry on the hex grid of a course.
+//!
+//! Positions are [`HexVector`]s, for calculations they are turned into [`CubeVector`]s which
+//! add a third, redundant axis. The axes follow the app: going [`Direction::East`] decreases `y`,
+//! going [`Direction::NorthWest`] increases `x` (see [`HexVector::neighbor`]). Rotations follow
+//! the hex rotations of tiles, a positive rotation turns [`Direction::East`] towards
+//! [`Direction::SouthEast`].
+use serde::Serialize;
+
+use crate::app::course::{Direction, HexVector};
+
+/// A position on the hex grid with a third axis, which makes rotations, reflections and distances
+/// symmetric. `x` and `y` are the same as in [`HexVector`] and `z` is `-x - y`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct CubeVector {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl From<&HexVector> for CubeVector {
+    fn from(hex: &HexVector) -> Self {
+        CubeVector {
+            x: hex.x,
+            y: hex.y,
+            z: -hex.x - hex.y,
+        }
+    }
+}
+
+impl From<CubeVector> for HexVector {
+    fn from(cube: CubeVector) -> Self {
+        HexVector::new(cube.x, cube.y)
+    }
+}
+
+impl CubeVector {
+    pub fn add(&self, other: &CubeVector) -> CubeVector {
+        CubeVector {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+
+    pub fn subtract(&self, other: &CubeVector) -> CubeVector {
+        CubeVector {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+
+    pub fn length(&self) -> i32 {
+        (self.x.abs() + self.y.abs() + self.z.abs()) / 2
+    }
+
+    /// Turns this around the origin by a number of hex rotations (60° steps).
+    pub fn rotated(&self, hex_rotation: i32) -> CubeVector {
+        (0..hex_rotation.rem_euclid(6)).fold(*self, |cube, _| CubeVector {
+            x: -cube.z,
+            y: -cube.x,
+            z: -cube.y,
+        })
+    }
+
+    /// Mirrors this at the line through the origin running [`Direction::East`] and
+    /// [`Direction::West`].
+    fn reflected_east_west(&self) -> CubeVector {
+        CubeVector {
+            x: -self.x,
+            y: -self.z,
+            z: -self.y,
+        }
+    }
+
+    /// The cube closest to a fractional position.
+    fn round(x: f64, y: f64, z: f64) -> CubeVector {
+        let (mut rx, mut ry, mut rz) = (x.round(), y.round(), z.round());
+        let (dx, dy, dz) = ((rx - x).abs(), (ry - y).abs(), (rz - z).abs());
+        // Rounding each axis on its own can leave the sum off zero, the axis that was rounded the
+        // most is the one to fix
+        if dx > dy && dx > dz {
+            rx = -ry - rz;
+        } else if dy > dz {
+            ry = -rx - rz;
+        } else {
+            rz = -rx - ry;
+        }
+        CubeVector {
+            x: rx as i32,
+            y: ry as i32,
+            z: rz as i32,
+        }
+    }
+}
+
+impl Direction {
+    /// The step from a hex to its neighbor in this direction.
+    pub fn cube_vector(&self) -> CubeVector {
+        CubeVector::from(&HexVector::new(0, 0).neighbor(self))
+    }
+}
+
+impl HexVector {
+    /// Turns this around `pivot` by a number of hex rotations (60° steps), in the same direction
+    /// as [`Direction::rotated`].
+    pub fn rotated(&self, pivot: &HexVector, hex_rotation: i32) -> HexVector {
+        let pivot = CubeVector::from(pivot);
+        let offset = CubeVector::from(self).subtract(&pivot);
+        offset.rotated(hex_rotation).add(&pivot).into()
+    }
+
+    /// Mirrors this at the line through `pivot` running in `axis` (and its opposite direction).
+    pub fn reflected(&self, pivot: &HexVector, axis: Direction) -> HexVector {
+        let pivot = CubeVector::from(pivot);
+        let rotation = axis.hex_rotation();
+        CubeVector::from(self)
+            .subtract(&pivot)
+            .rotated(-rotation)
+            .reflected_east_west()
+            .rotated(rotation)
+            .add(&pivot)
+            .into()
+    }
+
+    /// All hexes exactly `radius` steps away, going clockwise from the one [`Direction::East`].
+    /// A radius of 0 is just this hex.
+    pub fn ring(&self, radius: i32) -> Vec<HexVector> {
+        if radius <= 0 {
+            return vec![self.clone()];
+        }
+        let mut hex = self.hex_vector_in_distance(&Direction::East, radius);
+        let mut ring = Vec::new();
+        // The side between the corners in two neighboring directions runs two hex rotations
+        // further than the first of them, e.g. from the east corner towards the south west
+        for side in 0..6 {
+            let direction = Direction::SouthWest.rotated(side);
+            for _ in 0..radius {
+                ring.push(hex.clone());
+                hex = hex.neighbor(&direction);
+            }
+        }
+        ring
+    }
+
+    /// All hexes at most `radius` steps away, ring by ring starting with this one.
+    pub fn range(&self, radius: i32) -> Vec<HexVector> {
+        (0..=radius.max(0))
+            .flat_map(|radius| self.ring(radius))
+            .collect()
+    }
+
+    /// The hexes a straight line from the center of this hex to the center of `to` passes
+    /// through, including both ends.
+    pub fn line_to(&self, to: &HexVector) -> Vec<HexVector> {
+        let from = CubeVector::from(self);
+        let to = CubeVector::from(to);
+        let distance = from.subtract(&to).length();
+        if distance == 0 {
+            return vec![self.clone()];
+        }
+        // Lines along the edges between two hexes would otherwise round to either side at random,
+        // nudging them off the edge always picks the same side
+        let nudge = |value: i32, by: f64| f64::from(value) + by;
+        let (fx, fy, fz) = (
+            nudge(from.x, 1e-6),
+            nudge(from.y, 2e-6),
+            nudge(from.z, -3e-6),
+        );
+        let (tx, ty, tz) = (nudge(to.x, 1e-6), nudge(to.y, 2e-6), nudge(to.z, -3e-6));
+        (0..=distance)
+            .map(|step| {
+                let t = f64::from(step) / f64::from(distance);
+                CubeVector::round(fx + (tx - fx) * t, fy + (ty - fy) * t, fz + (tz - fz) * t).into()
+            })
+            .collect()
+    }
+
+    /// The direction to go in from this hex to reach `to` in a straight line.
+    ///
+    /// Returns `None` if `to` is this hex or is not in line with it in any of the six directions.
+    pub fn direction_to(&self, to: &HexVector) -> Option<Direction> {
+        let offset = CubeVector::from(to).subtract(&CubeVector::from(self));
+        let distance = offset.length();
+        if distance == 0 {
+            return None;
+        }
+        Direction::ALL.into_iter().find(|direction| {
+            let step = direction.cube_vector();
+            step.x * distance == offset.x
+                && step.y * distance == offset.y
+                && step.z * distance == offset.z
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::app::course::{Direction, HexVector};
+
+    /// Turning a neighbor around its cell is the same as turning the direction it is in, which
+    /// ties rotations to the hex rotations of tiles.
+    #[test]
+    fn rotations_follow_hex_rotations() {
+        let pivot = HexVector::new(3, -2);
+        for direction in Direction::ALL {
+            let neighbor = pivot.neighbor(&direction);
+            for hex_rotation in -7..=7 {
+                assert_eq!(
+                    neighbor.rotated(&pivot, hex_rotation),
+                    pivot.neighbor(&direction.rotated(hex_rotation)),
+                    "{direction:?} rotated by {hex_rotation}"
+                );
+            }
+        }
+
+        // East is one step along -y, a single rotation ends up south east, one step along -x
+        let east = HexVector::new(0, -2);
+        assert_eq!(
+            east.rotated(&HexVector::new(0, 0), 1),
+            HexVector::new(-2, 0)
+        );
+        assert_eq!(east.rotated(&HexVector::new(0, 0), 6), east);
+    }
+
+    #[test]
+    fn reflections_mirror_at_an_axis() {
+        let pivot = HexVector::new(-1, 4);
+        for axis in Direction::ALL {
+            for direction in Direction::ALL {
+                // A direction k hex rotations away from the axis ends up k hex rotations away on
+                // the other side
+                let offset = direction.hex_rotation() - axis.hex_rotation();
+                let hex = pivot.hex_vector_in_distance(&direction, 2);
+                let reflected = hex.reflected(&pivot, axis);
+                assert_eq!(
+                    reflected,
+                    pivot.hex_vector_in_distance(&axis.rotated(-offset), 2),
+                    "{direction:?} at {axis:?}"
+                );
+                assert_eq!(reflected.reflected(&pivot, axis), hex);
+            }
+        }
+    }
+
+    #[test]
+    fn rings_and_ranges() {
+        let center = HexVector::new(2, 1);
+        assert_eq!(center.ring(0), std::slice::from_ref(&center));
+
+        let ring = center.ring(2);
+        assert_eq!(ring.len(), 12);
+        assert_eq!(ring.iter().collect::<HashSet<_>>().len(), 12);
+        assert!(ring.iter().all(|hex| hex.distance(&center) == 2));
+        assert_eq!(ring[0], center.hex_vector_in_distance(&Direction::East, 2));
+        // Going clockwise the next corner is the south east one
+        assert_eq!(
+            ring[2],
+            center.hex_vector_in_distance(&Direction::SouthEast, 2)
+        );
+
+        let range = center.range(2);
+        assert_eq!(range.len(), 19);
+        assert_eq!(range.iter().collect::<HashSet<_>>().len(), 19);
+        assert!(range.iter().all(|hex| hex.distance(&center) <= 2));
+    }
+
+    #[test]
+    fn lines_step_from_neighbor_to_neighbor() {
+        let from = HexVector::new(0, 0);
+        assert_eq!(
+            from.line_to(&HexVector::new(3, -3)),
+            (0..=3)
+                .map(|step| from.hex_vector_in_distance(&Direction::NorthEast, step))
+                .collect::<Vec<_>>()
+        );
+
+        let to = HexVector::new(-4, 7);
+        let line = from.line_to(&to);
+        assert_eq!(line.len(), 8);
+        assert_eq!((line.first(), line.last()), (Some(&from), Some(&to)));
+        assert!(line.windows(2).all(|pair| pair[0].distance(&pair[1]) == 1));
+
+        assert_eq!(from.line_to(&from), std::slice::from_ref(&from));
+    }
+
+    #[test]
+    fn directions_between_hexes() {
+        let from = HexVector::new(1, -1);
+        for direction in Direction::ALL {
+            let to = from.hex_vector_in_distance(&direction, 3);
+            assert_eq!(from.direction_to(&to), Some(direction));
+        }
+        assert_eq!(from.direction_to(&from), None);
+        // One step north west and one west is not in line with any direction
+        let to = from
+            .neighbor(&Direction::NorthWest)
+            .neighbor(&Direction::West);
+        assert_eq!(from.direction_to(&to), None);
+        assert_eq!(from.distance(&to), 2);
+    }
+}
@@ -0,0 +1,255 @@
+//! Draws courses as text, to look at them in a terminal (e.g. over SSH or while debugging a
+//! fixture).
+//!
+//! Every retainer with cells (a layer, a balcony, ...) gets a map of its hex grid at world
+//! positions, north is up. A cell shows a short code for its topmost tile (see
+//! [`Cell::top_tile`]) and below it the direction the tile is turned towards and its height.
+//! The margin holds the position of the leftmost field of each row, every field to the right of
+//! it is one step [`Direction::East`]. Below the maps are the tile codes and the rails between
+//! the positions of their exits.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::app::course::{Direction, HexVector};
+use crate::app::layer::TileKind;
+use crate::app::scene::{Cell, RailExit, RetainerKind, Scene};
+
+/// Columns of text between two neighboring fields of a row, rows are shifted by half of it.
+const FIELD_WIDTH: usize = 6;
+
+/// The characters to draw with.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Charset {
+    /// Arrows for directions.
+    #[default]
+    Unicode,
+    /// Hex rotations (0 is east, 1 south east, ...) for directions, for terminals without
+    /// arrows.
+    Ascii,
+}
+
+impl Charset {
+    fn direction(&self, direction: Direction) -> char {
+        match self {
+            Charset::Unicode => match direction {
+                Direction::NorthEast => '↗',
+                Direction::East => '→',
+                Direction::SouthEast => '↘',
+                Direction::SouthWest => '↙',
+                Direction::West => '←',
+                Direction::NorthWest => '↖',
+            },
+            Charset::Ascii => char::from(b'0' + direction.hex_rotation() as u8),
+        }
+    }
+}
+
+/// The course as text, see the [module documentation](self).
+pub fn map(scene: &Scene, charset: Charset) -> String {
+    // Layers come first, in the order of the course, then everything else carrying cells
+    let mut retainer_ids: Vec<i32> = scene.layers.iter().map(|layer| layer.id).collect();
+    for cell in &scene.cells {
+        if !retainer_ids.contains(&cell.retainer_id) {
+            retainer_ids.push(cell.retainer_id);
+        }
+    }
+
+    let mut codes: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut text = String::new();
+    for retainer_id in retainer_ids {
+        let cells: Vec<&Cell> = scene
+            .cells
+            .iter()
+            .filter(|cell| cell.retainer_id == retainer_id)
+            .collect();
+        let _ = writeln!(text, "{}", heading(scene, retainer_id));
+        if cells.is_empty() {
+            let _ = writeln!(text, "  no cells\n");
+            continue;
+        }
+        for cell in &cells {
+            if let Some(tile) = cell.top_tile() {
+                codes
+                    .entry(code(&tile.kind))
+                    .or_default()
+                    .insert(format!("{:?}", tile.kind));
+            }
+        }
+        grid(&mut text, &cells, charset);
+        text.push('\n');
+    }
+
+    if !codes.is_empty() {
+        let _ = writeln!(text, "Tiles");
+        for (code, kinds) in &codes {
+            let kinds: Vec<_> = kinds.iter().map(String::as_str).collect();
+            let _ = writeln!(text, "  {code:<4} {}", kinds.join(", "));
+        }
+        text.push('\n');
+    }
+
+    let _ = writeln!(text, "Rails");
+    if scene.rails.is_empty() {
+        let _ = writeln!(text, "  none");
+    }
+    for rail in &scene.rails {
+        let _ = writeln!(
+            text,
+            "  [{}] {:?}: {} -> {}, span {}{}",
+            rail.index,
+            rail.kind,
+            exit(&rail.exit_1, charset),
+            exit(&rail.exit_2, charset),
+            rail.span(),
+            if rail.materialized {
+                ""
+            } else {
+                " (not built)"
+            },
+        );
+    }
+    text
+}
+
+fn heading(scene: &Scene, retainer_id: i32) -> String {
+    if let Some(layer) = scene.layers.iter().find(|layer| layer.id == retainer_id) {
+        return format!(
+            "Layer {} ({:?}) at height {}",
+            layer.id, layer.kind, layer.height.lower
+        );
+    }
+    match scene.retainer(retainer_id).map(|retainer| &retainer.kind) {
+        Some(RetainerKind::Balcony) => format!("Balcony {retainer_id}"),
+        Some(RetainerKind::Tile(kind)) => format!("{kind:?} {retainer_id}"),
+        Some(RetainerKind::Layer(kind)) => format!("Layer {retainer_id} ({kind:?})"),
+        None => format!("Retainer {retainer_id}"),
+    }
+}
+
+/// Draws the fields of the cells, two lines of text per row.
+fn grid(text: &mut String, cells: &[&Cell], charset: Charset) {
+    // Going north west is a row up and half a field to the left, going east a field to the
+    // right, which makes `-x` the row and `-2y - x` the column in half fields
+    let place = |position: &HexVector| (-position.x, -2 * position.y - position.x);
+    let rows = cells.iter().map(|cell| place(&cell.position).0);
+    let columns = cells.iter().map(|cell| place(&cell.position).1);
+    let (Some(first_row), Some(last_row)) = (rows.clone().min(), rows.max()) else {
+        return;
+    };
+    let first_column = columns.clone().min().unwrap_or_default();
+    let last_column = columns.max().unwrap_or_default();
+
+    for row in first_row..=last_row {
+        let x = -row;
+        // Fields of a row are on every other half field column
+        let leftmost = if (first_column - x).rem_euclid(2) == 0 {
+            first_column
+        } else {
+            first_column + 1
+        };
+        let y = -(leftmost + x) / 2;
+        let mut lines = [format!("{:>8} ", format!("({x}, {y})")), " ".repeat(9)];
+        for column in (leftmost..=last_column).step_by(2) {
+            let y = -(column + x) / 2;
+            let fields = match cells
+                .iter()
+                .find(|cell| cell.position.x == x && cell.position.y == y)
+            {
+                Some(cell) => field(cell, charset),
+                None => [String::new(), String::new()],
+            };
+            let offset = (column - first_column) as usize * FIELD_WIDTH / 2;
+            for (line, field) in lines.iter_mut().zip(fields) {
+                let width = 9 + offset - line.chars().count();
+                let _ = write!(line, "{}{field}", " ".repeat(width));
+            }
+        }
+        for line in lines {
+            let _ = writeln!(text, "{}", line.trim_end());
+        }
+    }
+}
+
+/// The code of the topmost tile of a cell, and the direction it is turned towards with its
+/// height.
+fn field(cell: &Cell, charset: Charset) -> [String; 2] {
+    match cell.top_tile() {
+        Some(tile) => [
+            code(&tile.kind),
+            format!(
+                "{}{}",
+                charset.direction(Direction::East.rotated(tile.hex_rotation)),
+                tile.height()
+            ),
+        ],
+        None => [".".to_string(), String::new()],
+    }
+}
+
+/// A short code for a kind of tile: its capital letters and digits (e.g. `STC` for
+/// `StackerTowerClosed`), or the first two letters if that is just one.
+pub fn code(kind: &TileKind) -> String {
+    let name = format!("{kind:?}");
+    let initials: String = name
+        .chars()
+        .filter(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        .take(4)
+        .collect();
+    if initials.len() >= 2 {
+        initials
+    } else {
+        name.chars().take(2).collect()
+    }
+}
+
+fn exit(exit: &RailExit, charset: Charset) -> String {
+    format!(
+        "({}, {}) {}",
+        exit.position.x,
+        exit.position.y,
+        charset.direction(Direction::East.rotated(exit.side_hex_rotation))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Charset, code, map};
+    use crate::app::fixtures::tower_scene;
+    use crate::app::layer::TileKind;
+
+    #[test]
+    fn maps_show_the_top_tile_of_every_cell() {
+        let scene = tower_scene();
+        let text = map(&scene, Charset::Unicode);
+
+        assert!(
+            text.contains("Layer 0 (BaseLayerPiece) at height -1\n"),
+            "{text}"
+        );
+        assert!(text.contains("Layer 1 (LargeLayer) at height 19\n  no cells\n"));
+        assert!(text.contains("Balcony 200\n"));
+        // The curve on the stacker tower is turned west, 17 small stackers up
+        let curve = scene.cells[0].position.clone();
+        let row = text
+            .lines()
+            .position(|line| line.trim_start().starts_with(&format!("({}, ", curve.x)))
+            .unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert!(lines[row].ends_with("Cu"), "{}", lines[row]);
+        assert!(lines[row + 1].ends_with("←17"), "{}", lines[row + 1]);
+        assert!(text.contains("  Cu   Curve\n"));
+        assert!(text.contains("  St   Starter\n"));
+        assert!(text.contains("Rails\n  [0] "));
+
+        let ascii = map(&scene, Charset::Ascii);
+        assert!(ascii.is_ascii());
+        assert!(ascii.contains("317"));
+    }
+
+    #[test]
+    fn codes_are_initials() {
+        assert_eq!(code(&TileKind::StackerTowerClosed), "STC");
+        assert_eq!(code(&TileKind::Curve), "Cu");
+        assert_eq!(code(&TileKind::Starter), "St");
+    }
+}
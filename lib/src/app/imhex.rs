@@ -0,0 +1,241 @@
+//! Generates a pattern for the [ImHex](https://github.com/WerWolv/ImHex) hex editor from the
+//! [`layout`] description, so course files can be inspected with the same structure the parser
+//! uses.
+//!
+//! The pattern in the repository (`murmelbahn.hexpat`) is generated by
+//! `cargo run -p murmelbahn-lib --example imhex_pattern > murmelbahn.hexpat`.
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::app::course::CourseSaveDataVersion;
+use crate::app::layout::{self, Condition, Enum, Field, Layout, Primitive, Struct, Type};
+
+/// The global variable holding the version of the course, conditions on the version refer to it.
+const VERSION_VARIABLE: &str = "current_save_data_version";
+
+/// Returns an ImHex pattern covering every supported
+/// [`CourseSaveDataVersion`](crate::app::course::CourseSaveDataVersion).
+pub fn pattern() -> String {
+    let mut definitions = Definitions::default();
+    let root = layout::saved_course();
+    definitions.collect(&root);
+
+    let mut out = String::new();
+    out.push_str(
+        "// ImHex pattern for course files of the GraviTrax app.\n\
+         // Generated from the parser in murmelbahn-lib, do not edit by hand:\n\
+         // cargo run -p murmelbahn-lib --example imhex_pattern > murmelbahn.hexpat\n\n",
+    );
+    for enumeration in &definitions.enums {
+        write_enum(&mut out, enumeration);
+    }
+    let _ = writeln!(out, "{} {VERSION_VARIABLE};\n", version_type());
+    for structure in &definitions.structs {
+        write_struct(&mut out, structure);
+    }
+    let _ = writeln!(out, "{} course @ 0x00;", root.qualified_name());
+    out
+}
+
+/// The name of the enum of the version.
+fn version_type() -> String {
+    type_name(&CourseSaveDataVersion::layout())
+}
+
+/// The enums and structs used by a layout, each type comes after the types it uses.
+#[derive(Default)]
+struct Definitions {
+    seen: HashSet<String>,
+    enums: Vec<Enum>,
+    structs: Vec<Struct>,
+}
+
+impl Definitions {
+    fn collect(&mut self, structure: &Struct) {
+        if !self.seen.insert(structure.qualified_name()) {
+            return;
+        }
+        self.collect_fields(&structure.fields);
+        self.structs.push(structure.clone());
+    }
+
+    fn collect_fields(&mut self, fields: &[Field]) {
+        for field in fields {
+            match field {
+                Field::Value { ty, .. } | Field::List { ty, .. } => self.collect_type(ty),
+                Field::If { fields, .. } => self.collect_fields(fields),
+                Field::String { .. } => {}
+            }
+        }
+    }
+
+    fn collect_type(&mut self, ty: &Type) {
+        match ty {
+            Type::Primitive(_) => {}
+            Type::Enum(enumeration) => {
+                if self.seen.insert(enumeration.qualified_name()) {
+                    self.enums.push(enumeration.clone());
+                }
+            }
+            Type::Struct(structure) => self.collect(&structure()),
+            Type::Switch(cases) => {
+                for (_, ty) in cases {
+                    self.collect_type(ty);
+                }
+            }
+        }
+    }
+}
+
+/// Opens the namespace of a qualified name, returns the indentation inside it and the name
+/// without the namespace.
+fn open_namespace(out: &mut String, qualified_name: &str) -> (usize, String) {
+    match qualified_name.rsplit_once("::") {
+        Some((namespace, name)) => {
+            let _ = writeln!(out, "namespace {namespace} {{");
+            (1, name.to_string())
+        }
+        None => (0, qualified_name.to_string()),
+    }
+}
+
+fn close_namespace(out: &mut String, indent: usize) {
+    if indent > 0 {
+        out.push_str("}\n");
+    }
+    out.push('\n');
+}
+
+fn write_enum(out: &mut String, enumeration: &Enum) {
+    let (indent, name) = open_namespace(out, &enumeration.qualified_name());
+    let pad = "    ".repeat(indent);
+    let _ = writeln!(out, "{pad}enum {name} : u32 {{");
+    for (name, value) in &enumeration.variants {
+        let _ = writeln!(out, "{pad}    {name} = {value},");
+    }
+    let _ = writeln!(out, "{pad}}};");
+    close_namespace(out, indent);
+}
+
+fn write_struct(out: &mut String, structure: &Struct) {
+    let (indent, name) = open_namespace(out, &structure.qualified_name());
+    let _ = writeln!(out, "{}struct {name} {{", "    ".repeat(indent));
+    write_fields(out, &structure.fields, indent + 1);
+    let _ = writeln!(out, "{}}};", "    ".repeat(indent));
+    close_namespace(out, indent);
+}
+
+fn write_fields(out: &mut String, fields: &[Field], indent: usize) {
+    let pad = "    ".repeat(indent);
+    for field in fields {
+        match field {
+            Field::Value {
+                name, ty, version, ..
+            } => {
+                if let Some(version) = version {
+                    let _ = writeln!(out, "{pad}{VERSION_VARIABLE} = {version};");
+                }
+                match ty {
+                    Type::Switch(cases) => write_switch(out, name, cases, indent),
+                    ty => {
+                        let _ = writeln!(out, "{pad}{} {name};", type_name(ty));
+                    }
+                }
+            }
+            Field::List { name, count, ty } => {
+                let _ = writeln!(out, "{pad}{} {name}[{count}];", type_name(ty));
+            }
+            Field::String { name, count, .. } => {
+                let _ = writeln!(out, "{pad}char {name}[{count}];");
+            }
+            Field::If { condition, fields } => {
+                let _ = writeln!(out, "{pad}if ({}) {{", condition_expression(condition));
+                write_fields(out, fields, indent + 1);
+                let _ = writeln!(out, "{pad}}}");
+            }
+        }
+    }
+}
+
+/// Writes the field `name` with the type of the case matching the version, versions with the
+/// same type share one case.
+fn write_switch(
+    out: &mut String,
+    name: &str,
+    cases: &[(Vec<CourseSaveDataVersion>, Type)],
+    indent: usize,
+) {
+    let mut grouped: Vec<(String, Vec<CourseSaveDataVersion>)> = Vec::new();
+    for (versions, ty) in cases {
+        let ty = type_name(ty);
+        match grouped.iter_mut().find(|(name, _)| *name == ty) {
+            Some((_, all)) => all.extend(versions),
+            None => grouped.push((ty, versions.clone())),
+        }
+    }
+    for (ty, versions) in grouped {
+        let pad = "    ".repeat(indent);
+        let _ = writeln!(
+            out,
+            "{pad}if ({}) {{",
+            condition_expression(&Condition::Version(versions))
+        );
+        let _ = writeln!(out, "{pad}    {ty} {name};");
+        let _ = writeln!(out, "{pad}}}");
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Primitive(Primitive::U8) => "u8".to_string(),
+        Type::Primitive(Primitive::U32) => "u32".to_string(),
+        Type::Primitive(Primitive::I32) => "s32".to_string(),
+        Type::Primitive(Primitive::U64) => "u64".to_string(),
+        Type::Primitive(Primitive::U128) => "u128".to_string(),
+        Type::Primitive(Primitive::F32) => "float".to_string(),
+        Type::Primitive(Primitive::Bool) => "bool".to_string(),
+        Type::Enum(enumeration) => enumeration.qualified_name(),
+        Type::Struct(structure) => structure().qualified_name(),
+        Type::Switch(_) => unreachable!("switches are written by write_switch"),
+    }
+}
+
+fn condition_expression(condition: &Condition) -> String {
+    match condition {
+        Condition::Version(versions) => versions
+            .iter()
+            .map(|version| format!("{VERSION_VARIABLE} == {}::{version:?}", version_type()))
+            .collect::<Vec<_>>()
+            .join(" || "),
+        Condition::Flag(flag) => flag.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pattern;
+
+    /// The pattern in the repository has to be regenerated whenever the parser changes.
+    #[test]
+    fn checked_in_pattern_is_up_to_date() {
+        assert_eq!(
+            include_str!("../../../murmelbahn.hexpat"),
+            pattern(),
+            "murmelbahn.hexpat is out of date, regenerate it with `cargo run -p murmelbahn-lib --example imhex_pattern > murmelbahn.hexpat`"
+        );
+    }
+
+    #[test]
+    fn pattern_covers_every_version_and_tile() {
+        let pattern = pattern();
+        assert!(pattern.contains("        SkyTrax = 7,\n"));
+        assert!(pattern.contains("        InitialLaunch = 100101,\n"));
+        assert!(pattern.contains("        Kst2In1L = "));
+        assert!(pattern.contains("namespace skytrax {\n    struct Layer {\n"));
+        assert!(pattern.contains(
+            "if (current_save_data_version == course::CourseSaveDataVersion::ZiplineAdded2019) {\n            ziplineadded2019::Course course;\n"
+        ));
+        assert!(pattern.contains("        current_save_data_version = header.version;\n"));
+        assert!(pattern.ends_with("course::SavedCourse course @ 0x00;\n"));
+    }
+}
@@ -0,0 +1,91 @@
+//! Courses saved before the zipline was added in 2019: save format versions `InitialLaunch`,
+//! `RailRework2018` and `PersistenceRefactor2019`.
+//!
+//! These are modelled on [`ziplineadded2019`](crate::app::ziplineadded2019) minus the parts that
+//! were introduced later. Only few courses in these formats are still around (mostly editorial
+//! and tutorial ones) and none of the following has been checked against a real course file yet,
+//! `tests/parse_all.rs` lists the versions the corpus has no fixture for:
+//!
+//! - Layers, cells and tiles are the same as in `ZiplineAdded2019`.
+//! - There are no ropes in any of these versions, they came with the zipline.
+//! - The element generation is only stored since `PersistenceRefactor2019`.
+//! - Rails only have the `materialized` flag since `PersistenceRefactor2019`
+//!   (`RailConstructionData::has_materialized`).
+
+use deku::prelude::*;
+use serde::Serialize;
+
+use crate::app::course::{CourseElementGeneration, CourseMetaData, CourseSaveDataVersion};
+use crate::app::layout::Layout;
+use crate::app::pillar::PillarConstructionData;
+use crate::app::rail::RailConstructionData;
+use crate::app::ziplineadded2019::LayerConstructionData;
+
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
+#[derive(Debug, Serialize)]
+#[deku(ctx = "version: CourseSaveDataVersion")]
+pub struct Course {
+    pub meta_data: CourseMetaData,
+
+    #[deku(temp, temp_value = "layer_construction_data.len() as u32")]
+    layer_construction_data_size: u32,
+    #[deku(count = "layer_construction_data_size")]
+    pub layer_construction_data: Vec<LayerConstructionData>,
+
+    #[deku(temp, temp_value = "rail_construction_data.len() as u32")]
+    rail_construction_data_size: u32,
+    #[deku(ctx = "version")]
+    #[deku(count = "rail_construction_data_size")]
+    pub rail_construction_data: Vec<RailConstructionData>,
+
+    #[deku(temp, temp_value = "pillar_construction_data.len() as u32")]
+    pillar_construction_data_size: u32,
+    #[deku(count = "pillar_construction_data_size")]
+    pub pillar_construction_data: Vec<PillarConstructionData>,
+
+    /// Only stored since `PersistenceRefactor2019`, older courses only contain elements from the
+    /// initial launch and the 2018 christmas update.
+    #[deku(
+        cond = "version == CourseSaveDataVersion::PersistenceRefactor2019",
+        default = "None"
+    )]
+    pub generation: Option<CourseElementGeneration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::BillOfMaterials;
+    use crate::app::course::{Course, SavedCourse};
+    use crate::app::fixtures::legacy_course;
+    use crate::app::layer::{LayerKind, TileKind};
+
+    #[test]
+    fn pre_2019_courses_parse_and_produce_a_bill_of_materials() {
+        for version in [100101, 100201, 1] {
+            let bytes = legacy_course(version);
+            let course = SavedCourse::from_bytes(&bytes)
+                .unwrap_or_else(|e| panic!("version {version} parses: {e}"));
+            assert!(course.header.version.is_supported());
+            let body = match &course.course {
+                Course::InitialLaunch(body)
+                | Course::RailRework2018(body)
+                | Course::PersistenceRefactor2019(body) => body,
+                other => panic!("unexpected course variant for version {version}: {other:?}"),
+            };
+            assert_eq!(body.generation.is_some(), version == 1);
+            assert_eq!(
+                course.to_bytes().unwrap(),
+                bytes,
+                "version {version} round-trips"
+            );
+
+            let bom = BillOfMaterials::try_from(course.course).unwrap();
+            assert_eq!(bom.layer_kind(LayerKind::BaseLayerPiece), Some(1));
+            assert_eq!(bom.tile_kind(TileKind::Starter), Some(1));
+            assert_eq!(bom.tile_kind(TileKind::Curve), Some(1));
+            assert_eq!(bom.tile_kind(TileKind::StackerSmall), Some(1));
+            assert_eq!(bom.rails_small, 1);
+        }
+    }
+}
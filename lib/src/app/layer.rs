@@ -1,11 +1,26 @@
+use std::io::{Seek, Write};
+
 use deku::prelude::*;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::app::course::CourseSaveDataVersion;
 use crate::app::course::HexVector;
+use crate::app::layout::Layout;
 
-#[derive(Clone, Debug, Deserialize, Eq, DekuRead, Hash, JsonSchema, PartialEq, Serialize)]
+#[derive(
+    Clone,
+    Debug,
+    Deserialize,
+    Eq,
+    DekuRead,
+    DekuWrite,
+    Hash,
+    JsonSchema,
+    Layout,
+    PartialEq,
+    Serialize,
+)]
 #[deku(id_type = "u32")]
 pub enum LayerKind {
     #[deku(id = "0")]
@@ -26,7 +41,19 @@ pub enum LayerKind {
     Unknown(u32),
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, DekuRead, Hash, JsonSchema, PartialEq, Serialize)]
+#[derive(
+    Clone,
+    Debug,
+    Deserialize,
+    Eq,
+    DekuRead,
+    DekuWrite,
+    Hash,
+    JsonSchema,
+    Layout,
+    PartialEq,
+    Serialize,
+)]
 #[deku(id_type = "u32")]
 pub enum TileKind {
     #[deku(id = "0")]
@@ -289,7 +316,31 @@ pub enum TileKind {
     Unknown(u32),
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+impl TileKind {
+    /// Tiles a marble is started from.
+    pub fn is_starter(&self) -> bool {
+        matches!(
+            self,
+            TileKind::Starter | TileKind::DomeStarter | TileKind::KstStarter
+        )
+    }
+
+    /// Stackers only lift other tiles, no marble ever runs through them.
+    /// Formats before 2020 list them as tiles, later ones store the height of each tile instead.
+    pub fn is_stacker(&self) -> bool {
+        matches!(
+            self,
+            TileKind::Stacker
+                | TileKind::StackerSmall
+                | TileKind::StackerBatch
+                | TileKind::LightStacker
+                | TileKind::LightStackerSmall
+                | TileKind::LightStackerBatch
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, DekuRead, DekuWrite, Eq, Layout, PartialEq, Serialize)]
 #[deku(id_type = "u32")]
 pub enum PowerSignalMode {
     Off = 0,
@@ -299,7 +350,7 @@ pub enum PowerSignalMode {
     Automatic = 4,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Clone, Copy, Debug, DekuRead, DekuWrite, Eq, Layout, PartialEq, Serialize)]
 #[deku(id_type = "u32")]
 pub enum LightStoneColorMode {
     Off = 0,
@@ -310,7 +361,8 @@ pub enum LightStoneColorMode {
     White = 5,
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct LayerConstructionData {
@@ -327,7 +379,7 @@ pub struct LayerConstructionData {
     /// This position is also the reference point (0/0) for the `local_hex_positions` from the `CellConstructionData`.
     pub world_hex_position: HexVector,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "cell_construction_datas.len() as i32")]
     cell_construction_datas_size: i32,
 
     #[deku(count = "cell_construction_datas_size")]
@@ -335,7 +387,7 @@ pub struct LayerConstructionData {
     pub cell_construction_datas: Vec<CellConstructionData>,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct CellConstructionData {
     /// This position is relative to the 0/0 position of the current layer
@@ -352,13 +404,14 @@ impl CellConstructionData {
     }
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct TileTowerTreeNodeData {
     pub index: i32,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "children.len() as i32")]
     pub children_count: i32,
 
     #[deku(ctx = "version")]
@@ -369,41 +422,83 @@ pub struct TileTowerTreeNodeData {
     pub children: Vec<TileTowerTreeNodeData>,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+/// The value the app stores in place of a retainer id for tiles that are not retainers.
+const NO_RETAINER_ID: i32 = -2147483647;
+
+/// The value the app stores in place of an optional mode (power signal, light stone colour) that
+/// is not set.
+pub(crate) const NO_MODE: u32 = 2147483648;
+
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct TileTowerConstructionData {
     pub kind: TileKind,
     pub height_in_small_stacker: i32,
     pub hex_rotation: i32,
     #[deku(map = "TileTowerConstructionData::map_retainer_id")]
+    #[deku(writer = "TileTowerConstructionData::write_retainer_id(deku::writer, *retainer_id)")]
     pub retainer_id: Option<i32>,
 
     #[deku(
-        cond = "version == CourseSaveDataVersion::Power2022 || version == CourseSaveDataVersion::LightStones2023 || version == CourseSaveDataVersion::SkyTrax",
+        cond = "TileTowerConstructionData::has_power_signal_mode(version)",
         default = "None"
     )]
     #[deku(map = "TileTowerConstructionData::map_power_signal_mode")]
+    #[deku(
+        writer = "TileTowerConstructionData::write_mode(deku::writer, TileTowerConstructionData::has_power_signal_mode(version), power_signal_mode.as_ref())"
+    )]
     pub power_signal_mode: Option<PowerSignalMode>,
 
     #[deku(
-        cond = "version == CourseSaveDataVersion::LightStones2023 || version == CourseSaveDataVersion::SkyTrax",
+        cond = "TileTowerConstructionData::has_light_stone_color_mode(version)",
         default = "None"
     )]
     #[deku(map = "TileTowerConstructionData::map_light_stone_color_mode")]
+    #[deku(
+        writer = "TileTowerConstructionData::write_mode(deku::writer, TileTowerConstructionData::has_light_stone_color_mode(version), light_stone_color_mode.as_ref())"
+    )]
     pub light_stone_color_mode: Option<LightStoneColorMode>,
 }
 
 impl TileTowerConstructionData {
+    /// Power signal modes were added with the Power update in 2022.
+    pub(crate) fn has_power_signal_mode(version: CourseSaveDataVersion) -> bool {
+        matches!(
+            version,
+            CourseSaveDataVersion::Power2022
+                | CourseSaveDataVersion::LightStones2023
+                | CourseSaveDataVersion::PreSkyTraxDuringDevelopment
+                | CourseSaveDataVersion::SkyTrax
+        )
+    }
+
+    /// Light stone colour modes were added with the light stones in 2023.
+    pub(crate) fn has_light_stone_color_mode(version: CourseSaveDataVersion) -> bool {
+        matches!(
+            version,
+            CourseSaveDataVersion::LightStones2023
+                | CourseSaveDataVersion::PreSkyTraxDuringDevelopment
+                | CourseSaveDataVersion::SkyTrax
+        )
+    }
+
     fn map_retainer_id(field: i32) -> Result<Option<i32>, DekuError> {
-        if field == -2147483647 {
+        if field == NO_RETAINER_ID {
             Ok(None)
         } else {
             Ok(Some(field))
         }
     }
 
+    fn write_retainer_id<W: Write + Seek>(
+        writer: &mut Writer<W>,
+        retainer_id: Option<i32>,
+    ) -> Result<(), DekuError> {
+        retainer_id.unwrap_or(NO_RETAINER_ID).to_writer(writer, ())
+    }
+
     fn map_power_signal_mode(field: u32) -> Result<Option<PowerSignalMode>, DekuError> {
-        if field == 2147483648 {
+        if field == NO_MODE {
             Ok(None)
         } else {
             let input = field.to_le_bytes();
@@ -413,7 +508,7 @@ impl TileTowerConstructionData {
     }
 
     fn map_light_stone_color_mode(field: u32) -> Result<Option<LightStoneColorMode>, DekuError> {
-        if field == 2147483648 {
+        if field == NO_MODE {
             Ok(None)
         } else {
             let input = field.to_le_bytes();
@@ -421,6 +516,20 @@ impl TileTowerConstructionData {
             Ok(Some(LightStoneColorMode::try_from(input2)?))
         }
     }
+
+    /// Writes an optional mode, using the "not set" marker for `None`.
+    /// Nothing is written if the save format version doesn't have this field (`present` is false).
+    fn write_mode<W: Write + Seek, T: DekuWriter>(
+        writer: &mut Writer<W>,
+        present: bool,
+        mode: Option<&T>,
+    ) -> Result<(), DekuError> {
+        match (present, mode) {
+            (false, _) => Ok(()),
+            (true, None) => NO_MODE.to_writer(writer, ()),
+            (true, Some(mode)) => mode.to_writer(writer, ()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,231 @@
+//! A description of the on-disk layout of every course format.
+//!
+//! deku generates the parser from the attributes on our structs but offers no way to inspect
+//! those attributes at runtime. `#[derive(Layout)]` (from `murmelbahn-derive`) reads the same
+//! attributes and describes the layout as data: which fields a struct has, in which order, which
+//! lists they contain and which fields only exist in some versions. It is used to locate parse
+//! failures ([`crate::app::diagnostics`]) and to generate the ImHex pattern
+//! ([`crate::app::imhex`]).
+//!
+//! Every type the parser reads derives `Layout`. On structs using `#[deku_derive]` the derive has
+//! to come before that attribute, it removes the temporary fields and the `#[deku]` attributes.
+use std::io::Cursor;
+
+use deku::prelude::*;
+use deku::reader::Reader;
+pub use murmelbahn_derive::Layout;
+
+use crate::app::course::{CourseSaveDataVersion, SavedCourse};
+
+/// A type of which the on-disk layout is known, see the [module documentation](self).
+pub trait Layout {
+    fn layout() -> Type;
+}
+
+/// A fixed size value as stored on disk (little endian).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Primitive {
+    U8,
+    U32,
+    I32,
+    U64,
+    U128,
+    F32,
+    Bool,
+}
+
+macro_rules! primitive_layout {
+    ($($ty:ty => $primitive:ident),*) => {
+        $(
+            impl Layout for $ty {
+                fn layout() -> Type {
+                    Type::Primitive(Primitive::$primitive)
+                }
+            }
+        )*
+    };
+}
+
+primitive_layout!(u8 => U8, u32 => U32, i32 => I32, u64 => U64, u128 => U128, f32 => F32, bool => Bool);
+
+/// An enum stored as its `u32` discriminant.
+#[derive(Clone, Debug)]
+pub struct Enum {
+    /// The path of the module the enum is defined in, see [`Enum::qualified_name`].
+    pub module: &'static str,
+    pub name: &'static str,
+
+    /// The named variants and their discriminants.
+    pub variants: Vec<(&'static str, u32)>,
+
+    /// Whether a catch-all variant like `Unknown(u32)` takes every other discriminant.
+    pub catch_all: bool,
+}
+
+impl Enum {
+    /// The name of the enum prefixed with its module, e.g. `layer::TileKind`.
+    pub fn qualified_name(&self) -> String {
+        qualified_name(self.module, self.name)
+    }
+
+    /// Whether `raw` can be read as this enum.
+    pub fn accepts(&self, raw: u32) -> bool {
+        self.catch_all || self.variants.iter().any(|(_, id)| *id == raw)
+    }
+}
+
+/// The type of a field.
+#[derive(Clone, Debug)]
+pub enum Type {
+    Primitive(Primitive),
+    Enum(Enum),
+    /// A function because structs can contain themselves (e.g. `TileTowerTreeNodeData`).
+    Struct(fn() -> Struct),
+    /// An enum of which the variant is chosen by the version (e.g.
+    /// [`Course`](crate::app::course::Course)), nothing of the enum itself is stored.
+    Switch(Vec<(Vec<CourseSaveDataVersion>, Type)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Struct {
+    /// The path of the module the struct is defined in, see [`Struct::qualified_name`].
+    pub module: &'static str,
+    pub name: &'static str,
+    pub fields: Vec<Field>,
+}
+
+impl Struct {
+    /// The name of the struct prefixed with its module, e.g. `skytrax::Layer`. Different formats
+    /// use structs of the same name.
+    pub fn qualified_name(&self) -> String {
+        qualified_name(self.module, self.name)
+    }
+}
+
+fn qualified_name(module: &str, name: &str) -> String {
+    let module = module.rsplit("::").next().unwrap_or(module);
+    format!("{module}::{name}")
+}
+
+/// Reads a value and passes it to the `map` function of its field, like the parser does.
+pub type MappedReader = for<'a, 'b> fn(&mut Reader<'a, Cursor<&'b [u8]>>) -> Result<(), DekuError>;
+
+#[derive(Clone, Debug)]
+pub enum Field {
+    Value {
+        name: &'static str,
+        ty: Type,
+
+        /// Set for fields with a `map` function, `ty` is the type the value is mapped to then.
+        mapped: Option<MappedReader>,
+
+        /// The field of the same struct (e.g. `header.version`) holding the version for this
+        /// value and everything in it.
+        version: Option<&'static str>,
+    },
+
+    /// A list of values, the number of values is stored in the field `count` of the same struct.
+    List {
+        name: &'static str,
+        count: &'static str,
+        ty: Type,
+    },
+
+    /// A string, the number of bytes is stored in the field `count` of the same struct.
+    String {
+        name: &'static str,
+        count: &'static str,
+        decode: fn(Vec<u8>) -> Result<(), DekuError>,
+    },
+
+    /// Fields which only exist if the condition holds.
+    If {
+        condition: Condition,
+        fields: Vec<Field>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub enum Condition {
+    /// The course is in one of these versions.
+    Version(Vec<CourseSaveDataVersion>),
+    /// The `bool` field of this name in the same struct is true.
+    Flag(&'static str),
+}
+
+impl Condition {
+    /// The versions for which `predicate` is true.
+    pub fn versions(predicate: impl Fn(CourseSaveDataVersion) -> bool) -> Condition {
+        Condition::Version(
+            CourseSaveDataVersion::ALL
+                .into_iter()
+                .filter(|version| predicate(*version))
+                .collect(),
+        )
+    }
+}
+
+/// The layout of a whole course file (without the trailing checksum).
+pub fn saved_course() -> Struct {
+    let Type::Struct(saved_course) = SavedCourse::layout() else {
+        unreachable!("SavedCourse is a struct");
+    };
+    saved_course()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use deku::prelude::*;
+
+    use super::{Layout, Type};
+    use crate::app::course::{CourseElementGeneration, CourseSaveDataVersion};
+    use crate::app::layer::{LightStoneColorMode, TileKind};
+    use crate::app::rail::RailKind;
+
+    /// Every variant of the description is read by deku as the variant of the same name.
+    fn assert_variants_match_parser<T>()
+    where
+        T: Layout + for<'a> TryFrom<&'a [u8], Error = DekuError> + Debug,
+    {
+        let Type::Enum(enumeration) = T::layout() else {
+            panic!("{} is not an enum", std::any::type_name::<T>());
+        };
+        assert!(!enumeration.variants.is_empty());
+        for (name, id) in &enumeration.variants {
+            let parsed = T::try_from(id.to_le_bytes().as_slice()).expect("variant parses");
+            assert_eq!(format!("{parsed:?}"), *name);
+        }
+    }
+
+    #[test]
+    fn enum_variants_match_the_parser() {
+        assert_variants_match_parser::<CourseSaveDataVersion>();
+        assert_variants_match_parser::<CourseElementGeneration>();
+        assert_variants_match_parser::<TileKind>();
+        assert_variants_match_parser::<RailKind>();
+        assert_variants_match_parser::<LightStoneColorMode>();
+    }
+
+    #[test]
+    fn catch_all_variants_take_every_discriminant() {
+        let Type::Enum(tile_kind) = TileKind::layout() else {
+            panic!("TileKind is an enum");
+        };
+        assert!(tile_kind.catch_all);
+        assert!(tile_kind.accepts(76));
+        assert!(
+            !tile_kind
+                .variants
+                .iter()
+                .any(|(name, _)| name.contains("Unknown"))
+        );
+
+        let Type::Enum(version) = CourseSaveDataVersion::layout() else {
+            panic!("CourseSaveDataVersion is an enum");
+        };
+        assert!(!version.catch_all);
+        assert!(!version.accepts(8));
+    }
+}
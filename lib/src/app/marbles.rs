@@ -0,0 +1,281 @@
+//! How many marbles a course needs, worked out from the tiles and how they are connected.
+//!
+//! Marbles are counted where they have to be placed before a run: one in every starter and the
+//! ones preloaded into cannons, lifts and similar tiles. Marbles that arrive at a tile over a rail
+//! are already counted where they started.
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::app::course::HexVector;
+use crate::app::graph::Graph;
+use crate::app::layer::{LightStoneColorMode, TileKind};
+use crate::app::scene::Scene;
+use crate::physical::Element;
+
+#[derive(Debug, Default, Serialize)]
+pub struct MarbleRequirement {
+    /// How many marbles the course needs, of any colour.
+    pub total: i32,
+    /// Colours at least one marble has to have, because tiles react to them.
+    /// These marbles are part of [`total`](Self::total).
+    pub colors: BTreeSet<MarbleColor>,
+    /// How many different colours are needed, e.g. two for a preloaded colour swap which only
+    /// does something if the arriving marble has a different colour.
+    pub distinct_colors: i32,
+    /// Every tile that needs marbles and how many.
+    pub tiles: Vec<TileMarbles>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum MarbleColor {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TileMarbles {
+    pub kind: TileKind,
+    pub position: HexVector,
+    pub marbles: i32,
+}
+
+impl MarbleColor {
+    pub const ALL: [MarbleColor; 3] = [MarbleColor::Red, MarbleColor::Green, MarbleColor::Blue];
+
+    fn from_light_stone_color_mode(mode: LightStoneColorMode) -> Option<MarbleColor> {
+        match mode {
+            LightStoneColorMode::Red => Some(MarbleColor::Red),
+            LightStoneColorMode::Green => Some(MarbleColor::Green),
+            LightStoneColorMode::Blue => Some(MarbleColor::Blue),
+            // White and alternating light up for any marble
+            LightStoneColorMode::Off
+            | LightStoneColorMode::Alternating
+            | LightStoneColorMode::White => None,
+        }
+    }
+
+    pub fn element(&self) -> Element {
+        match self {
+            MarbleColor::Red => Element::MarbleRed,
+            MarbleColor::Green => Element::MarbleGreen,
+            MarbleColor::Blue => Element::MarbleBlue,
+        }
+    }
+}
+
+impl MarbleRequirement {
+    /// One colour for each of the [`distinct_colors`](Self::distinct_colors): the
+    /// [`colors`](Self::colors) tiles react to, then as many others as are still missing.
+    pub fn marble_colors(&self) -> impl Iterator<Item = MarbleColor> + '_ {
+        let missing = (self.distinct_colors.max(0) as usize).saturating_sub(self.colors.len());
+        let others = MarbleColor::ALL
+            .into_iter()
+            .filter(|color| !self.colors.contains(color))
+            .take(missing);
+        self.colors.iter().copied().chain(others)
+    }
+}
+
+impl From<&Scene> for MarbleRequirement {
+    fn from(scene: &Scene) -> Self {
+        let graph = Graph::from(scene);
+        let mut requirement = MarbleRequirement::default();
+
+        for (index, node) in graph.nodes.iter().enumerate() {
+            let outgoing = graph.successors(index).count() as i32;
+            let marbles = marbles_for(&node.kind, outgoing);
+            if marbles > 0 {
+                requirement.total += marbles;
+                requirement.tiles.push(TileMarbles {
+                    kind: node.kind.clone(),
+                    position: node.position.clone(),
+                    marbles,
+                });
+            }
+
+            let tile = &scene.cells[node.cell].tiles[node.tile];
+            if let Some(color) = tile
+                .light_stone_color_mode
+                .and_then(MarbleColor::from_light_stone_color_mode)
+            {
+                requirement.colors.insert(color);
+            }
+            if node.kind == TileKind::ColorSwapPreloaded {
+                requirement.distinct_colors = requirement.distinct_colors.max(2);
+            }
+        }
+
+        let colors = requirement.colors.len() as i32;
+        requirement.distinct_colors = requirement.distinct_colors.max(colors);
+        // Even if nothing needs to be preloaded, every coloured marble has to be somewhere
+        requirement.total = requirement.total.max(requirement.distinct_colors);
+        requirement
+    }
+}
+
+/// How many marbles have to be placed in a tile with `outgoing` ways out (rails and neighbors)
+/// before a run.
+///
+/// Tiles that set marbles off count them from the graph, tiles that hold marbles as part of the
+/// piece need the same number wherever they are.
+fn marbles_for(kind: &TileKind, outgoing: i32) -> i32 {
+    // Every way out needs a marble of its own, the marble that sets the tile off stays in it.
+    // Catapults and volcanos let no marble out sideways, they throw the one they hold.
+    let outgoing = outgoing.max(1);
+    match kind {
+        TileKind::Starter | TileKind::DomeStarter | TileKind::KstStarter => 1,
+        TileKind::ZiplineStart => 1,
+        TileKind::Cannon
+        | TileKind::Splash
+        | TileKind::Volcano
+        | TileKind::Spinner
+        | TileKind::Catapult
+        | TileKind::TipTube => outgoing,
+        // A marble running into a lift pushes the column of marbles in its tube up and the top
+        // one out, so the tube has to be full however the lift is connected. The counts are how
+        // many marbles fill the small and the large tube.
+        TileKind::LiftSmall => 5,
+        TileKind::LiftLarge => 8,
+        // Each of the two halves of the bridge is held up by a marble of its own, whichever way
+        // marbles run over it
+        TileKind::Bridge => 2,
+        // The marble of the other colour that is swapped out by the first one arriving
+        TileKind::ColorSwapPreloaded => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MarbleColor, MarbleRequirement};
+    use crate::app::course::Direction;
+    use crate::app::fixtures::{tile, tower_scene};
+    use crate::app::layer::{LightStoneColorMode, TileKind};
+    use crate::app::scene::{Cell, Scene};
+
+    /// The tower scene with the curve on the tower turned into `kind` and the starter moved next
+    /// to its west side, at the same height.
+    fn scene_around(kind: TileKind) -> Scene {
+        let mut scene = tower_scene();
+        let position = scene.cells[0].position.neighbor(&Direction::West);
+        scene.cells[0].tiles[1].kind = kind;
+        let starter_cell = &mut scene.cells[1];
+        starter_cell.position = position;
+        starter_cell.tiles[0].base_height = 16;
+        starter_cell.tiles[0].height_in_small_stacker = Some(1);
+        scene
+    }
+
+    /// Puts a tile next to the tile on the tower, at the same height.
+    fn place_next_to_tower(scene: &mut Scene, direction: Direction, kind: TileKind) {
+        let position = scene.cells[0].position.neighbor(&direction);
+        scene.cells.push(Cell {
+            retainer_id: 0,
+            position,
+            tiles: vec![tile(kind, 17)],
+        });
+    }
+
+    fn marbles_in(requirement: &MarbleRequirement, kind: TileKind) -> i32 {
+        requirement
+            .tiles
+            .iter()
+            .filter(|tile| tile.kind == kind)
+            .map(|tile| tile.marbles)
+            .sum()
+    }
+
+    #[test]
+    fn starters_need_a_marble() {
+        let requirement = MarbleRequirement::from(&tower_scene());
+        assert_eq!(requirement.total, 1);
+        assert_eq!(requirement.tiles.len(), 1);
+        assert_eq!(requirement.tiles[0].kind, TileKind::Starter);
+        assert!(requirement.colors.is_empty());
+    }
+
+    #[test]
+    fn cannons_need_a_marble_per_way_out() {
+        // The starter runs into the west side of the cannon, which shoots a marble out of its
+        // east side into a goal basin
+        let mut scene = scene_around(TileKind::Cannon);
+        place_next_to_tower(&mut scene, Direction::East, TileKind::GoalBasin);
+        let requirement = MarbleRequirement::from(&scene);
+        assert_eq!(marbles_in(&requirement, TileKind::Cannon), 1);
+        assert_eq!(requirement.total, 2);
+    }
+
+    #[test]
+    fn splashes_need_a_marble_per_way_out() {
+        // Marbles fall into the splash from above, at rotation 3 it lets them out to the west,
+        // north east and south east. The starter to the west only runs into it.
+        let mut scene = scene_around(TileKind::Splash);
+        place_next_to_tower(&mut scene, Direction::NorthEast, TileKind::GoalBasin);
+        place_next_to_tower(&mut scene, Direction::SouthEast, TileKind::GoalBasin);
+        let requirement = MarbleRequirement::from(&scene);
+        assert_eq!(marbles_in(&requirement, TileKind::Splash), 2);
+        assert_eq!(requirement.total, 3);
+    }
+
+    #[test]
+    fn colored_marbles_are_counted() {
+        let mut scene = scene_around(TileKind::Splash);
+        scene.cells[1].tiles[0].light_stone_color_mode = Some(LightStoneColorMode::Red);
+
+        // The splash is connected to nothing it can let marbles out to, so it needs a single
+        // marble
+        let requirement = MarbleRequirement::from(&scene);
+        assert_eq!(requirement.total, 2);
+        assert_eq!(
+            requirement.colors.iter().copied().collect::<Vec<_>>(),
+            [MarbleColor::Red]
+        );
+        assert_eq!(requirement.distinct_colors, 1);
+
+        // A preloaded colour swap needs a second colour
+        scene.cells[0].tiles[1].kind = TileKind::ColorSwapPreloaded;
+        let requirement = MarbleRequirement::from(&scene);
+        assert_eq!(requirement.total, 2);
+        assert_eq!(requirement.distinct_colors, 2);
+        assert_eq!(
+            requirement.marble_colors().collect::<Vec<_>>(),
+            [MarbleColor::Red, MarbleColor::Green]
+        );
+    }
+
+    #[test]
+    fn catapults_and_tip_tubes_need_a_marble_per_way_out() {
+        // The catapult throws the marble it holds, it has no way out to count
+        let requirement = MarbleRequirement::from(&scene_around(TileKind::Catapult));
+        assert_eq!(marbles_in(&requirement, TileKind::Catapult), 1);
+
+        let mut scene = scene_around(TileKind::TipTube);
+        scene.cells[0].tiles[1].hex_rotation = 0;
+        place_next_to_tower(&mut scene, Direction::East, TileKind::GoalBasin);
+        let requirement = MarbleRequirement::from(&scene);
+        assert_eq!(marbles_in(&requirement, TileKind::TipTube), 1);
+        assert_eq!(requirement.total, 2);
+    }
+
+    #[test]
+    fn lifts_and_bridges_need_the_marbles_they_hold() {
+        for (kind, marbles) in [
+            (TileKind::LiftSmall, 5),
+            (TileKind::LiftLarge, 8),
+            (TileKind::Bridge, 2),
+        ] {
+            // The same whether the tile is connected to nothing or on the way to a goal basin
+            let mut scene = scene_around(kind.clone());
+            let requirement = MarbleRequirement::from(&scene);
+            assert_eq!(marbles_in(&requirement, kind.clone()), marbles, "{kind:?}");
+
+            scene.cells[0].tiles[1].hex_rotation = 0;
+            place_next_to_tower(&mut scene, Direction::East, TileKind::GoalBasin);
+            let requirement = MarbleRequirement::from(&scene);
+            assert_eq!(marbles_in(&requirement, kind.clone()), marbles, "{kind:?}");
+            assert_eq!(requirement.total, marbles + 1, "{kind:?}");
+        }
+    }
+}
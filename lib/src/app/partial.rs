@@ -0,0 +1,229 @@
+//! Lenient parsing: recovers as much of a course as possible when its body can't be parsed.
+//!
+//! Every format starts with the same header and metadata, so even when a newer app release
+//! changes the layout of the body we can usually still show the title and version of a course.
+//! Layers are read one by one until the first one fails.
+use std::fmt::Display;
+use std::io::Cursor;
+
+use deku::prelude::*;
+use deku::reader::Reader;
+use serde::{Serialize, Serializer};
+
+use crate::app::course::{
+    CourseElementGeneration, CourseMetaData, CourseSaveDataVersion, Error, SaveDataHeader,
+};
+use crate::app::diagnostics::Diagnostics;
+use crate::app::layer::LayerConstructionData;
+use crate::app::skytrax;
+use crate::app::ziplineadded2019::LayerConstructionData as ZiplineLayerConstructionData;
+
+/// Everything that could be read from a course whose body failed to parse.
+#[derive(Debug, Serialize)]
+pub struct PartialCourse {
+    /// The raw version from the header, also available if it is not a known
+    /// [`CourseSaveDataVersion`].
+    pub version_raw: Option<u32>,
+
+    /// Only available if the version is a known [`CourseSaveDataVersion`].
+    pub header: Option<SaveDataHeader>,
+    pub meta_data: Option<CourseMetaData>,
+
+    /// Only available for formats which store it before the layers (SkyTrax).
+    pub generation: Option<CourseElementGeneration>,
+
+    /// The layers that could be read before the first one failed.
+    /// Only available if the version is known.
+    pub layers: Option<PartialLayers>,
+
+    /// Why the full parse failed.
+    #[serde(serialize_with = "serialize_display")]
+    pub error: Error,
+
+    /// Where the full parse failed, if it could be determined.
+    pub diagnostics: Option<Diagnostics>,
+}
+
+/// The layers of a [`PartialCourse`], these differ per format.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum PartialLayers {
+    /// `InitialLaunch` up to `ZiplineAdded2019`
+    Zipline(Vec<ZiplineLayerConstructionData>),
+    /// `Pro2020` up to `LightStones2023`
+    Power(Vec<LayerConstructionData>),
+    /// `PreSkyTraxDuringDevelopment` and `SkyTrax`
+    SkyTrax(Vec<skytrax::Layer>),
+}
+
+impl PartialLayers {
+    pub fn len(&self) -> usize {
+        match self {
+            PartialLayers::Zipline(layers) => layers.len(),
+            PartialLayers::Power(layers) => layers.len(),
+            PartialLayers::SkyTrax(layers) => layers.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl PartialCourse {
+    /// Reads as much as possible from `bytes`, `error` is the error the full parse failed with.
+    pub(crate) fn read(bytes: &[u8], error: Error) -> PartialCourse {
+        let mut partial = PartialCourse {
+            version_raw: None,
+            header: None,
+            meta_data: None,
+            generation: None,
+            layers: None,
+            diagnostics: error.diagnostics().cloned(),
+            error,
+        };
+
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = Reader::new(&mut cursor);
+
+        let Ok(guid) = u128::from_reader_with_ctx(&mut reader, ()) else {
+            return partial;
+        };
+        let Ok(version_raw) = u32::from_reader_with_ctx(&mut reader, ()) else {
+            return partial;
+        };
+        partial.version_raw = Some(version_raw);
+        let version = CourseSaveDataVersion::try_from(version_raw.to_le_bytes().as_slice()).ok();
+        partial.header = version.map(|version| SaveDataHeader { guid, version });
+
+        let Ok(meta_data) = CourseMetaData::from_reader_with_ctx(&mut reader, ()) else {
+            return partial;
+        };
+        partial.meta_data = Some(meta_data);
+
+        let Some(version) = version else {
+            return partial;
+        };
+
+        partial.layers = Some(match version {
+            CourseSaveDataVersion::InitialLaunch
+            | CourseSaveDataVersion::RailRework2018
+            | CourseSaveDataVersion::PersistenceRefactor2019
+            | CourseSaveDataVersion::ZiplineAdded2019 => {
+                PartialLayers::Zipline(read_layers::<_, u32, _>(&mut reader, ()))
+            }
+            CourseSaveDataVersion::Pro2020
+            | CourseSaveDataVersion::Power2022
+            | CourseSaveDataVersion::LightStones2023 => {
+                PartialLayers::Power(read_layers::<_, u32, _>(&mut reader, version))
+            }
+            CourseSaveDataVersion::PreSkyTraxDuringDevelopment | CourseSaveDataVersion::SkyTrax => {
+                let Ok(generation) = CourseElementGeneration::from_reader_with_ctx(&mut reader, ())
+                else {
+                    return partial;
+                };
+                partial.generation = Some(generation);
+                PartialLayers::SkyTrax(read_layers::<_, i32, _>(&mut reader, version))
+            }
+        });
+
+        partial
+    }
+}
+
+/// Reads the layer count (of type `C`) followed by as many layers as possible.
+fn read_layers<T, C, Ctx>(reader: &mut Reader<Cursor<&[u8]>>, ctx: Ctx) -> Vec<T>
+where
+    T: for<'a> DekuReader<'a, Ctx>,
+    C: for<'a> DekuReader<'a> + TryInto<usize>,
+    Ctx: Copy,
+{
+    let Some(count) = C::from_reader_with_ctx(reader, ())
+        .ok()
+        .and_then(|count| count.try_into().ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut layers = Vec::new();
+    for _ in 0..count {
+        match T::from_reader_with_ctx(reader, ctx) {
+            Ok(layer) => layers.push(layer),
+            Err(_) => break,
+        }
+    }
+    layers
+}
+
+fn serialize_display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::course::SavedCourse;
+    use crate::app::fixtures::RawCourse;
+
+    /// A course from a newer app release with a version we don't know still shows its title.
+    #[test]
+    fn unknown_version_keeps_meta_data() {
+        let bytes = RawCourse::default()
+            .header(8)
+            .meta_data("From the future")
+            .bytes(&[0xAB; 16])
+            .0;
+
+        let partial = SavedCourse::from_bytes_lenient(&bytes).expect_err("body can't be parsed");
+        assert_eq!(partial.version_raw, Some(8));
+        assert!(partial.header.is_none());
+        assert_eq!(partial.meta_data.unwrap().title, "From the future");
+        assert!(partial.layers.is_none());
+    }
+
+    /// The layers before the one that fails are kept.
+    #[test]
+    fn layers_before_the_failure_are_kept() {
+        let bytes = RawCourse::default()
+            .header(4)
+            .meta_data("Broken")
+            .u32(2) // layers
+            .i32(0)
+            .u32(0) // base layer piece
+            .f32(-0.36)
+            .i32(0)
+            .i32(0)
+            .i32(0) // no cells
+            .i32(1)
+            .u32(2) // large layer
+            .f32(3.6)
+            .0; // truncated
+
+        let partial = SavedCourse::from_bytes_lenient(&bytes).expect_err("course is truncated");
+        assert_eq!(partial.version_raw, Some(4));
+        assert!(partial.header.is_some());
+        assert_eq!(partial.meta_data.unwrap().title, "Broken");
+        assert_eq!(partial.layers.unwrap().len(), 1);
+        assert_eq!(
+            partial.diagnostics.unwrap().path,
+            "course.layer_construction_data[1].world_hex_position.y"
+        );
+    }
+
+    #[test]
+    fn complete_course_is_returned_as_is() {
+        let bytes = RawCourse::default()
+            .header(4)
+            .meta_data("Complete")
+            .u32(0)
+            .u32(0)
+            .u32(0)
+            .u32(8)
+            .i32(0)
+            .with_checksum();
+
+        assert!(SavedCourse::from_bytes_lenient(&bytes).is_ok());
+    }
+}
@@ -3,8 +3,9 @@ use deku::prelude::*;
 use serde::Serialize;
 
 use crate::app::course::HexVector;
+use crate::app::layout::Layout;
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 pub struct PillarConstructionData {
     pub lower_layer_id: i32,
     pub lower_cell_local_position: HexVector,
@@ -0,0 +1,405 @@
+//! Where every piece of a course is in space, in millimetres.
+//!
+//! The [`Scene`] knows positions as cells on the hex grid and heights in small stackers, this
+//! turns them into [`Pose`]s for renderers and exporters. `x` points [`Direction::East`], `y`
+//! points north (between [`Direction::NorthWest`] and [`Direction::NorthEast`]) and `z` points
+//! up. The origin is the center of the cell at `(0, 0)` at height 0.
+//!
+//! The dimensions of the pieces are approximations, they are not taken from any official
+//! specification.
+use serde::Serialize;
+
+use crate::app::course::{Direction, HexVector, VertexVector};
+use crate::app::layer::{LayerKind, TileKind};
+use crate::app::rail::RailKind;
+use crate::app::scene::{RailExit, Scene, TILE_HEIGHT, TileRef};
+use crate::catalogue;
+
+/// The distance between the centers of two neighboring cells.
+pub const CELL_DISTANCE_MM: f32 = 50.0;
+
+/// The distance from the center of a cell to its corners.
+pub const CELL_RADIUS_MM: f32 = CELL_DISTANCE_MM / 1.732_050_8;
+
+/// The height of a small stacker, a large stacker is two of them.
+pub const SMALL_STACKER_HEIGHT_MM: f32 = 7.5;
+
+/// A position in millimetres and a rotation around the vertical axis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    /// The height of the bottom of the piece.
+    pub z: f32,
+    /// Degrees counterclockwise (seen from above) from [`Direction::East`], in `0..360`.
+    pub yaw: f32,
+}
+
+impl Pose {
+    /// The center of a cell at a height in small stackers, facing east.
+    pub fn at(position: &HexVector, height: i32) -> Pose {
+        let (x, y) = center(position.x as f32, position.y as f32);
+        Pose {
+            x,
+            y,
+            z: small_stackers(height),
+            yaw: 0.0,
+        }
+    }
+
+    /// The same pose, turned by a number of hex rotations (60° steps) from east.
+    pub fn turned(self, hex_rotation: i32) -> Pose {
+        Pose {
+            yaw: (-60 * hex_rotation).rem_euclid(360) as f32,
+            ..self
+        }
+    }
+
+    /// The same pose, facing `direction`.
+    pub fn facing(self, direction: Direction) -> Pose {
+        self.turned(direction.hex_rotation())
+    }
+
+    /// The same pose, moved horizontally by `distance` millimetres in the direction it faces.
+    pub fn forward(self, distance: f32) -> Pose {
+        let (sin, cos) = self.yaw.to_radians().sin_cos();
+        Pose {
+            x: self.x + cos * distance,
+            y: self.y + sin * distance,
+            ..self
+        }
+    }
+}
+
+/// The center of a cell from its (possibly fractional) hex coordinates.
+fn center(x: f32, y: f32) -> (f32, f32) {
+    // One step along -y is one cell east, one step along +x is one cell north west
+    let east = -y - x / 2.0;
+    let north = x * 3f32.sqrt() / 2.0;
+    (east * CELL_DISTANCE_MM, north * CELL_DISTANCE_MM)
+}
+
+/// The poses of all pieces of a [`Scene`].
+#[derive(Debug, Default, Serialize)]
+pub struct Poses {
+    pub layers: Vec<LayerPose>,
+    pub tiles: Vec<TilePose>,
+    pub columns: Vec<ColumnPose>,
+    pub rails: Vec<RailPose>,
+    pub walls: Vec<WallPose>,
+    pub balconies: Vec<BalconyPose>,
+    pub connectors: Vec<ConnectorPose>,
+}
+
+/// A layer, posed at its reference cell (see [`scene::Layer::position`]).
+#[derive(Debug, Serialize)]
+pub struct LayerPose {
+    pub id: i32,
+    pub kind: LayerKind,
+    pub pose: Pose,
+    /// The thickness of the layer.
+    pub height: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TilePose {
+    pub tile: TileRef,
+    pub kind: TileKind,
+    /// The pose of the bottom of the tile, on top of the stackers below it.
+    pub pose: Pose,
+    /// How tall the piece is, see [`catalogue::TileSpec::height`].
+    pub height: f32,
+}
+
+/// Stackers piled up in a cell, below a tile or as a pillar between two retainers.
+#[derive(Debug, Serialize)]
+pub struct ColumnPose {
+    pub origin: ColumnOrigin,
+    pub pose: Pose,
+    pub height: f32,
+    /// Whether the column rises from a light base and is lit.
+    pub lit: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum ColumnOrigin {
+    /// The stackers below a tile.
+    Tile(TileRef),
+    /// A pillar, by its index in the course file.
+    Pillar(usize),
+}
+
+#[derive(Debug, Serialize)]
+pub struct RailPose {
+    /// The index of the rail in the course file.
+    pub index: usize,
+    pub kind: RailKind,
+    /// Where the rail leaves both cells, at the middle of their sides and facing out of them.
+    pub exits: [Pose; 2],
+}
+
+#[derive(Debug, Serialize)]
+pub struct WallPose {
+    /// The index of the wall in the course file.
+    pub index: usize,
+    /// At the first stacker tower, facing the second one.
+    pub pose: Pose,
+    /// The distance between the centers of both stacker towers.
+    pub length: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalconyPose {
+    pub retainer_id: i32,
+    /// The index of the wall in the course file.
+    pub wall: usize,
+    /// At the cell in front of the balcony, facing away from the wall.
+    pub pose: Pose,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectorPose {
+    /// The index of the connector in the course file.
+    pub index: usize,
+    /// At the corner of the cells, connectors have no direction.
+    pub pose: Pose,
+}
+
+impl From<&Scene> for Poses {
+    fn from(scene: &Scene) -> Self {
+        let mut poses = Poses::default();
+
+        for layer in &scene.layers {
+            poses.layers.push(LayerPose {
+                id: layer.id,
+                kind: layer.kind.clone(),
+                pose: Pose::at(&layer.position, layer.height.lower),
+                height: small_stackers(layer.height.upper - layer.height.lower),
+            });
+        }
+
+        for (cell_index, cell) in scene.cells.iter().enumerate() {
+            for (tile_index, tile) in cell.tiles.iter().enumerate() {
+                let tile_ref = TileRef {
+                    cell: cell_index,
+                    tile: tile_index,
+                };
+                if let Some(height) = tile.height_in_small_stacker.filter(|&height| height > 0) {
+                    poses.columns.push(ColumnPose {
+                        origin: ColumnOrigin::Tile(tile_ref),
+                        pose: Pose::at(&cell.position, tile.base_height),
+                        height: small_stackers(height),
+                        lit: tile.on_light_base,
+                    });
+                }
+                poses.tiles.push(TilePose {
+                    tile: tile_ref,
+                    kind: tile.kind.clone(),
+                    pose: Pose::at(&cell.position, tile.height()).turned(tile.hex_rotation),
+                    height: small_stackers(catalogue::tile(&tile.kind).height),
+                });
+            }
+        }
+
+        for pillar in &scene.pillars {
+            poses.columns.push(ColumnPose {
+                origin: ColumnOrigin::Pillar(pillar.index),
+                pose: Pose::at(&pillar.position, pillar.base_height),
+                height: small_stackers(pillar.height_in_small_stacker),
+                lit: pillar.on_light_base,
+            });
+        }
+
+        for rail in scene.rails.iter().filter(|rail| rail.materialized) {
+            poses.rails.push(RailPose {
+                index: rail.index,
+                kind: rail.kind.clone(),
+                exits: [
+                    rail_exit_pose(scene, &rail.exit_1),
+                    rail_exit_pose(scene, &rail.exit_2),
+                ],
+            });
+        }
+
+        for wall in &scene.walls {
+            poses.walls.push(WallPose {
+                index: wall.index,
+                pose: Pose::at(&wall.tower_1_position, wall.bottom).facing(wall.direction),
+                length: wall.tower_1_position.distance(&wall.tower_2_position) as f32
+                    * CELL_DISTANCE_MM,
+            });
+            for balcony in &wall.balconies {
+                let height = scene
+                    .retainer(balcony.retainer_id)
+                    .and_then(|retainer| retainer.height)
+                    .map_or(wall.bottom, |height| height.lower);
+                poses.balconies.push(BalconyPose {
+                    retainer_id: balcony.retainer_id,
+                    wall: wall.index,
+                    pose: Pose::at(&balcony.position, height).facing(balcony.facing),
+                });
+            }
+        }
+
+        for connector in &scene.connectors {
+            poses.connectors.push(ConnectorPose {
+                index: connector.index,
+                pose: vertex_pose(&connector.vertex, connector.height),
+            });
+        }
+
+        poses
+    }
+}
+
+fn small_stackers(height: i32) -> f32 {
+    height as f32 * SMALL_STACKER_HEIGHT_MM
+}
+
+/// A vertex is the sum of the three cells meeting at it, their average is the corner.
+fn vertex_pose(vertex: &VertexVector, height: i32) -> Pose {
+    let (x, y) = center(vertex.x as f32 / 3.0, vertex.y as f32 / 3.0);
+    Pose {
+        x,
+        y,
+        z: small_stackers(height),
+        yaw: 0.0,
+    }
+}
+
+/// Rail exits sit at their stored height above their retainer. Formats before 2020 don't store
+/// it, their exits sit on the tile they are attached to ([`Scene::rail_exit_tile`]) or on the
+/// retainer if there is none.
+fn rail_exit_pose(scene: &Scene, exit: &RailExit) -> Pose {
+    let retainer_height = scene
+        .retainer(exit.retainer_id)
+        .and_then(|retainer| retainer.height)
+        .map(|height| height.upper);
+    let mut pose = Pose::at(&exit.position, 0)
+        .turned(exit.side_hex_rotation)
+        .forward(CELL_DISTANCE_MM / 2.0);
+    pose.z = match (retainer_height, exit.local_height) {
+        // The height is stored in the units of the app, where a small stacker is `TILE_HEIGHT`
+        // high
+        (Some(base), Some(local_height)) => {
+            small_stackers(base) + local_height / TILE_HEIGHT * SMALL_STACKER_HEIGHT_MM
+        }
+        _ => {
+            let tile_height = scene
+                .rail_exit_tile(exit)
+                .map(|tile| scene.cells[tile.cell].tiles[tile.tile].height());
+            small_stackers(tile_height.or(retainer_height).unwrap_or(0))
+        }
+    };
+    pose
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CELL_DISTANCE_MM, ColumnOrigin, Pose, Poses, SMALL_STACKER_HEIGHT_MM};
+    use crate::app::course::{Corner, Direction, HexVector, SavedCourse, VertexVector};
+    use crate::app::fixtures::skytrax_course;
+    use crate::app::fixtures::tower_scene;
+    use crate::app::layer::TileKind;
+    use crate::app::scene::Scene;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    /// Walking forward from a cell in a direction ends up at the neighbor in that direction.
+    #[test]
+    fn yaws_point_at_neighbors() {
+        let cell = HexVector::new(2, -3);
+        for direction in Direction::ALL {
+            let moved = Pose::at(&cell, 0)
+                .facing(direction)
+                .forward(CELL_DISTANCE_MM);
+            let neighbor = Pose::at(&cell.neighbor(&direction), 0);
+            assert_close(moved.x, neighbor.x);
+            assert_close(moved.y, neighbor.y);
+        }
+
+        // East is along x, north east is to the right and up
+        assert_close(Pose::at(&HexVector::new(0, -1), 0).x, CELL_DISTANCE_MM);
+        assert_close(
+            Pose::at(&HexVector::new(0, 0), 0)
+                .facing(Direction::East)
+                .yaw,
+            0.0,
+        );
+        assert_close(
+            Pose::at(&HexVector::new(0, 0), 0)
+                .facing(Direction::NorthEast)
+                .yaw,
+            60.0,
+        );
+        let north_east = Pose::at(&HexVector::new(1, -1), 0);
+        assert!(north_east.x > 0.0 && north_east.y > 0.0);
+    }
+
+    #[test]
+    fn pieces_are_posed() {
+        let scene = tower_scene();
+        let poses = Poses::from(&scene);
+        let tower = Pose::at(&scene.cells[0].position, 0);
+
+        let curve = poses
+            .tiles
+            .iter()
+            .find(|tile| tile.kind == TileKind::Curve)
+            .unwrap();
+        assert_close(curve.pose.x, tower.x);
+        assert_close(curve.pose.z, 17.0 * SMALL_STACKER_HEIGHT_MM);
+        // Three hex rotations turn it to face west
+        assert_close(curve.pose.yaw, 180.0);
+
+        // The small stacker between the stacker tower and the curve
+        let column = poses
+            .columns
+            .iter()
+            .find(|column| column.origin == ColumnOrigin::Tile(curve.tile))
+            .unwrap();
+        assert_close(column.pose.z, 16.0 * SMALL_STACKER_HEIGHT_MM);
+        assert_close(column.height, SMALL_STACKER_HEIGHT_MM);
+        assert!(
+            poses
+                .columns
+                .iter()
+                .any(|column| column.origin == ColumnOrigin::Pillar(0))
+        );
+
+        // The rail leaves the curve on its east side, half a cell away from its center
+        let exit = poses.rails[0].exits[0];
+        assert_close(exit.x, tower.x + CELL_DISTANCE_MM / 2.0);
+        assert_close(exit.y, tower.y);
+        assert_close(exit.yaw, 0.0);
+        assert_close(exit.z, curve.pose.z);
+
+        assert_eq!(poses.layers.len(), scene.layers.len());
+        assert_eq!(poses.walls.len(), 1);
+        assert_eq!(poses.balconies.len(), 1);
+    }
+
+    #[test]
+    fn connectors_are_at_the_corner_of_their_cells() {
+        let course = SavedCourse::from_bytes(&skytrax_course().with_checksum())
+            .unwrap()
+            .course;
+        let (mut scene, _) = Scene::resolve(&course);
+        scene.connectors[0].vertex = VertexVector::new(&HexVector::new(6, -2), Corner::South);
+        let poses = Poses::from(&scene);
+
+        let connector = &scene.connectors[0];
+        let pose = poses.connectors[0].pose;
+        assert_close(pose.z, connector.height as f32 * SMALL_STACKER_HEIGHT_MM);
+        for cell in connector.vertex.cells().unwrap() {
+            let center = Pose::at(&cell, 0);
+            let distance = (pose.x - center.x).hypot(pose.y - center.y);
+            assert_close(distance, CELL_DISTANCE_MM / 3f32.sqrt());
+        }
+    }
+}
@@ -3,8 +3,21 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::app::course::{CourseSaveDataVersion, HexVector};
+use crate::app::layout::Layout;
 
-#[derive(Clone, Debug, Deserialize, Eq, DekuRead, Hash, JsonSchema, PartialEq, Serialize)]
+#[derive(
+    Clone,
+    Debug,
+    Deserialize,
+    Eq,
+    DekuRead,
+    DekuWrite,
+    Hash,
+    JsonSchema,
+    Layout,
+    PartialEq,
+    Serialize,
+)]
 #[deku(id_type = "u32")]
 pub enum RailKind {
     Straight = 0,
@@ -31,20 +44,34 @@ pub enum RailKind {
     KstSlide120R = 22,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct RailConstructionExitIdentifier {
     pub retainer_id: i32,
     pub cell_local_hex_pos: HexVector,
     pub side_hex_rot: i32,
     #[deku(
-        cond = "version == CourseSaveDataVersion::Power2022 || version == CourseSaveDataVersion::Pro2020 || version == CourseSaveDataVersion::LightStones2023 || version == CourseSaveDataVersion::SkyTrax",
+        cond = "RailConstructionExitIdentifier::has_exit_local_pos_y(version)",
         default = "None"
     )]
     pub exit_local_pos_y: Option<f32>,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+impl RailConstructionExitIdentifier {
+    /// The height of an exit was added with the Pro release in 2020.
+    pub(crate) fn has_exit_local_pos_y(version: CourseSaveDataVersion) -> bool {
+        matches!(
+            version,
+            CourseSaveDataVersion::Pro2020
+                | CourseSaveDataVersion::Power2022
+                | CourseSaveDataVersion::LightStones2023
+                | CourseSaveDataVersion::PreSkyTraxDuringDevelopment
+                | CourseSaveDataVersion::SkyTrax
+        )
+    }
+}
+
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct RailConstructionData {
     #[deku(ctx = "version")]
@@ -53,8 +80,19 @@ pub struct RailConstructionData {
     pub exit_2_identifier: RailConstructionExitIdentifier,
     pub rail_kind: RailKind,
     #[deku(
-        cond = "version == CourseSaveDataVersion::ZiplineAdded2019",
+        cond = "RailConstructionData::has_materialized(version)",
         default = "None"
     )]
     pub materialized: Option<bool>,
 }
+
+impl RailConstructionData {
+    /// Only the two formats from 2019 store whether a rail is materialized.
+    pub(crate) fn has_materialized(version: CourseSaveDataVersion) -> bool {
+        matches!(
+            version,
+            CourseSaveDataVersion::PersistenceRefactor2019
+                | CourseSaveDataVersion::ZiplineAdded2019
+        )
+    }
+}
@@ -0,0 +1,1307 @@
+//! A version independent view of a course.
+//!
+//! Every save format stores the same things (layers, tiles, rails, ...) in a slightly different
+//! shape and all positions are relative to the layer (or balcony) they are on.
+//! A [`Scene`] resolves world positions and heights (in small stackers) once, so everything
+//! working with the contents of a course (e.g. the [`BillOfMaterials`](crate::app::BillOfMaterials))
+//! can be written once instead of per version.
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+use snafu::prelude::*;
+use tracing::trace;
+
+use crate::app::course::{Course, Direction, HexVector, VertexVector};
+use crate::app::layer::{
+    CellConstructionData, LayerConstructionData, LayerKind, LightStoneColorMode, PowerSignalMode,
+    TileKind, TileTowerTreeNodeData,
+};
+use crate::app::pillar::PillarConstructionData;
+use crate::app::rail::{RailConstructionData, RailConstructionExitIdentifier, RailKind};
+use crate::app::wall::{WallConstructionData, WallCoordinate, WallSide};
+use crate::app::ziplineadded2019::{
+    CellConstructionData as ZiplineCellConstructionData,
+    LayerConstructionData as ZiplineLayerConstructionData, RopeConstructionData, RopeKind,
+};
+use crate::app::{power2022, skytrax};
+use crate::catalogue::{self, RetainerSpec};
+
+#[derive(Debug, Serialize, Snafu)]
+pub enum Error {
+    #[snafu(display("{element} refers to unknown retainer [{retainer_id}]"))]
+    UnknownRetainerError {
+        retainer_id: i32,
+        element: CourseElement,
+    },
+
+    #[snafu(display("{element} needs the height of retainer [{retainer_id}] which is not known"))]
+    UnknownRetainerHeightError {
+        retainer_id: i32,
+        element: CourseElement,
+    },
+
+    #[snafu(display("{element} refers to unknown layer number [{layer_index}]"))]
+    UnknownLayerIndexError {
+        layer_index: u32,
+        element: CourseElement,
+    },
+
+    #[snafu(display("{element} does not run in a straight line between its stacker towers"))]
+    WallNotStraightError { element: CourseElement },
+}
+
+/// The part of a course an [`Error`] was found in, by its index in the course file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum CourseElement {
+    Rail(usize),
+    Pillar(usize),
+    Wall(usize),
+    Rope(usize),
+}
+
+impl Display for CourseElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CourseElement::Rail(index) => write!(f, "Rail [{index}]"),
+            CourseElement::Pillar(index) => write!(f, "Pillar [{index}]"),
+            CourseElement::Wall(index) => write!(f, "Wall [{index}]"),
+            CourseElement::Rope(index) => write!(f, "Rope [{index}]"),
+        }
+    }
+}
+
+// 0.36 is a magic number and it represents the height of a small stacker (in the App at least)
+pub(crate) const TILE_HEIGHT: f32 = 0.36;
+
+/// Balconies are attached to a wall in rows, each row one large stacker above the one below it.
+/// Row 0 is at the height the stacker towers carrying the wall stand at.
+const WALL_ROW_HEIGHT: i32 = 2;
+
+/// A course with world positions and heights resolved.
+///
+/// All heights are in small stackers.
+#[derive(Debug, Default, Serialize)]
+pub struct Scene {
+    pub layers: Vec<Layer>,
+    /// The cells of all layers followed by the cells on balconies.
+    pub cells: Vec<Cell>,
+    pub rails: Vec<Rail>,
+    pub walls: Vec<Wall>,
+    pub pillars: Vec<Pillar>,
+    /// Zipline ropes, only the 2019 format has them.
+    pub ropes: Vec<Rope>,
+    /// SkyTrax connectors, empty for older formats.
+    pub connectors: Vec<Connector>,
+    /// Everything other things are placed on, by retainer id.
+    pub retainers: BTreeMap<i32, Retainer>,
+}
+
+impl Scene {
+    pub fn retainer(&self, id: i32) -> Option<&Retainer> {
+        self.retainers.get(&id)
+    }
+
+    /// The height of a rail exit, rounded to small stackers. `None` for formats before 2020,
+    /// which don't store it, or if the height of the retainer is not known.
+    pub fn rail_exit_height(&self, exit: &RailExit) -> Option<i32> {
+        let base = self.retainer(exit.retainer_id)?.height?.upper;
+        Some(base + (exit.local_height? / TILE_HEIGHT).round() as i32)
+    }
+
+    /// The tile marbles run through that a rail exit is attached to.
+    ///
+    /// Exits only name the cell. If their height is known (see [`Scene::rail_exit_height`])
+    /// they are on the highest tile at or below it, or the lowest tile if they are below all of
+    /// them. Otherwise they are on the highest tile of the cell.
+    pub fn rail_exit_tile(&self, exit: &RailExit) -> Option<TileRef> {
+        let mut tiles: Vec<_> = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| {
+                cell.retainer_id == exit.retainer_id && cell.position == exit.position
+            })
+            .flat_map(|(cell_index, cell)| {
+                cell.tiles
+                    .iter()
+                    .enumerate()
+                    .map(move |(tile_index, tile)| {
+                        let tile_ref = TileRef {
+                            cell: cell_index,
+                            tile: tile_index,
+                        };
+                        (tile_ref, tile.height(), &tile.kind)
+                    })
+            })
+            .filter(|(_, _, kind)| !catalogue::tile(kind).is_closed())
+            .map(|(tile_ref, height, _)| (tile_ref, height))
+            .collect();
+        tiles.sort_by_key(|(_, height)| *height);
+
+        let tile = match self.rail_exit_height(exit) {
+            Some(exit_height) => tiles
+                .iter()
+                .rev()
+                .find(|(_, height)| *height <= exit_height)
+                .or(tiles.first()),
+            None => tiles.last(),
+        };
+        tile.map(|(tile_ref, _)| *tile_ref)
+    }
+}
+
+/// A 'retainer' is anything that can "hold" or "retain" other tiles or items.
+/// Base layers for example but also balconies, light bases and other things.
+///
+/// Positions of cells, rails, walls and pillars are stored relative to their retainer.
+#[derive(Debug, Serialize)]
+pub struct Retainer {
+    pub id: i32,
+    pub kind: RetainerKind,
+    /// The world position of the retainer, for tiles and balconies this is their cell.
+    pub position: HexVector,
+    /// `None` if the height could not be worked out.
+    pub height: Option<RetainerHeight>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum RetainerKind {
+    Layer(LayerKind),
+    /// A tile that other tiles can be built on, e.g. a stacker tower, a light base or a double
+    /// balcony.
+    Tile(TileKind),
+    /// A balcony on a wall.
+    Balcony,
+}
+
+/// The height of a retainer (e.g. a layer or a stacker tower).
+/// It keeps the height of the lower end as well as the upper end separately to account for
+/// different thicknesses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct RetainerHeight {
+    pub lower: i32,
+    pub upper: i32,
+}
+
+impl RetainerHeight {
+    pub fn new(lower: i32, upper: i32) -> RetainerHeight {
+        RetainerHeight { lower, upper }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Layer {
+    /// Layers are retainers, this is their retainer id.
+    pub id: i32,
+    pub kind: LayerKind,
+    /// The world position of the reference cell of the layer.
+    /// For base layers this is the one green cell in the corner, for the (hexagonal) clear layers
+    /// it is the cell in the middle.
+    pub position: HexVector,
+    /// The upper end is the height things are built on.
+    pub height: RetainerHeight,
+}
+
+/// A cell with everything that is stacked on it.
+#[derive(Debug, Serialize)]
+pub struct Cell {
+    /// The retainer this cell is on, a layer or a balcony.
+    pub retainer_id: i32,
+    pub position: HexVector,
+    /// The tiles of the cell, a tile always comes after the tile it is stacked on.
+    pub tiles: Vec<Tile>,
+}
+
+impl Cell {
+    /// The highest tile that is not a stacker, or the highest stacker if there is nothing else.
+    pub fn top_tile(&self) -> Option<&Tile> {
+        self.tiles
+            .iter()
+            .filter(|tile| !tile.kind.is_stacker())
+            .max_by_key(|tile| tile.height())
+            .or_else(|| self.tiles.iter().max_by_key(|tile| tile.height()))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tile {
+    pub kind: TileKind,
+    pub hex_rotation: i32,
+    /// The index (in [`Cell::tiles`]) of the tile this one is stacked on.
+    pub parent: Option<usize>,
+    /// The height the stackers below this tile start at.
+    pub base_height: i32,
+    /// The small stackers between `base_height` and this tile.
+    ///
+    /// The formats before 2020 list stackers as tiles of their own, they are turned into this
+    /// height. `None` for stackers of those formats with no tile on top of them, they are kept
+    /// as tiles.
+    pub height_in_small_stacker: Option<i32>,
+    /// Whether the stackers below this tile rise from a light base and are lit.
+    pub on_light_base: bool,
+    /// Set if the tile is a retainer (e.g. a stacker tower or a light base).
+    pub retainer_id: Option<i32>,
+    pub power_signal_mode: Option<PowerSignalMode>,
+    pub light_stone_color_mode: Option<LightStoneColorMode>,
+}
+
+impl Tile {
+    /// The height this tile sits at.
+    pub fn height(&self) -> i32 {
+        self.base_height + self.height_in_small_stacker.unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rail {
+    /// The index of the rail in the course file.
+    pub index: usize,
+    pub kind: RailKind,
+    pub exit_1: RailExit,
+    pub exit_2: RailExit,
+    /// Only the formats from 2019 store this, rails that are not materialized are not part of
+    /// the course.
+    pub materialized: bool,
+}
+
+impl Rail {
+    /// The number of fields between both exits, 0 if the exits are adjacent.
+    pub fn span(&self) -> i32 {
+        self.exit_1.position.distance(&self.exit_2.position) - 1
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RailExit {
+    /// The retainer (e.g. a layer) the cell of this exit is on.
+    pub retainer_id: i32,
+    pub position: HexVector,
+    pub side_hex_rotation: i32,
+    /// The height of the exit within its tile, only stored since 2020.
+    pub local_height: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Wall {
+    /// The index of the wall in the course file.
+    pub index: usize,
+    pub tower_1_retainer_id: i32,
+    pub tower_1_position: HexVector,
+    pub tower_2_retainer_id: i32,
+    pub tower_2_position: HexVector,
+    /// The direction from tower 1 to tower 2.
+    pub direction: Direction,
+    /// The height the wall stands at, the higher of the heights its stacker towers stand at.
+    pub bottom: i32,
+    pub balconies: Vec<Balcony>,
+}
+
+impl Wall {
+    /// The number of fields between both towers.
+    pub fn length(&self) -> i32 {
+        self.tower_1_position.distance(&self.tower_2_position) - 1
+    }
+
+    /// The number of rows of the wall, up to the row above its highest balcony.
+    pub fn rows(&self) -> i32 {
+        self.balconies
+            .iter()
+            .map(|balcony| balcony.coordinate.row + 1)
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Balcony {
+    /// Balconies are retainers, this is their retainer id.
+    pub retainer_id: i32,
+    pub side: WallSide,
+    pub coordinate: WallCoordinate,
+    /// The world position of the cell in front of the balcony.
+    pub position: HexVector,
+    /// The direction the balcony faces, from the wall towards its cell.
+    pub facing: Direction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Pillar {
+    /// The index of the pillar in the course file.
+    pub index: usize,
+    /// The retainer (e.g. a layer or a stacker tower) the pillar stands on.
+    pub lower_retainer_id: i32,
+    pub upper_layer_id: i32,
+    pub position: HexVector,
+    /// The height the stackers of the pillar start at (the top of the lower retainer).
+    pub base_height: i32,
+    /// The small stackers between both retainers.
+    pub height_in_small_stacker: i32,
+    /// Whether the pillar rises from a light base and is lit.
+    pub on_light_base: bool,
+}
+
+/// A SkyTrax connector, joining pieces at a corner of their cells.
+#[derive(Debug, Serialize)]
+pub struct Connector {
+    /// The index of the connector in the course file.
+    pub index: usize,
+    pub vertex: VertexVector,
+    pub height: i32,
+    /// The tiles meeting at the vertex at the height of the connector.
+    pub joins: Vec<TileRef>,
+}
+
+/// A tile by the index of its cell in [`Scene::cells`] and its index in [`Cell::tiles`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct TileRef {
+    pub cell: usize,
+    pub tile: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rope {
+    /// The index of the rope in the course file.
+    pub index: usize,
+    pub kind: RopeKind,
+    pub start: RopeEnd,
+    pub end: RopeEnd,
+}
+
+impl Rope {
+    /// The number of fields between both ends.
+    pub fn span(&self) -> i32 {
+        self.start.position.distance(&self.end.position) - 1
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RopeEnd {
+    /// The layer the zipline tile is on.
+    pub retainer_id: i32,
+    pub position: HexVector,
+}
+
+impl TryFrom<&Course> for Scene {
+    type Error = Error;
+
+    /// Fails if the course refers to retainers that don't exist, which only happens for corrupted
+    /// (or hand-crafted) courses.
+    fn try_from(course: &Course) -> Result<Self, Error> {
+        let (scene, errors) = Scene::resolve(course);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(scene),
+        }
+    }
+}
+
+impl Scene {
+    /// Resolves as much of a course as possible.
+    ///
+    /// Rails, pillars and walls referring to retainers that don't exist are left out, the
+    /// returned errors say which ones.
+    pub fn resolve(course: &Course) -> (Scene, Vec<Error>) {
+        let mut builder = SceneBuilder::default();
+
+        match course {
+            Course::InitialLaunch(course)
+            | Course::RailRework2018(course)
+            | Course::PersistenceRefactor2019(course) => {
+                builder.add_zipline_layers(&course.layer_construction_data);
+                builder.add_pillars(&course.pillar_construction_data);
+                builder.add_rails(&course.rail_construction_data);
+            }
+            Course::ZiplineAdded2019(course) => {
+                builder.add_zipline_layers(&course.layer_construction_data);
+                builder.add_pillars(&course.pillar_construction_data);
+                builder.add_rails(&course.rail_construction_data);
+                builder.add_ropes(&course.rope_construction_data);
+            }
+            Course::Power2022(course)
+            | Course::Pro2020(course)
+            | Course::LightStones2023(course) => {
+                let power2022::Course {
+                    layer_construction_data,
+                    rail_construction_data,
+                    pillar_construction_data,
+                    wall_construction_data,
+                    ..
+                } = course;
+                builder.add_layers(layer_construction_data);
+                // Pillars can rise from balconies, so walls go first
+                builder.add_walls(wall_construction_data);
+                builder.add_pillars(pillar_construction_data);
+                builder.add_rails(rail_construction_data);
+            }
+            Course::PreSkyTrax(course) | Course::SkyTrax(course) => {
+                builder.add_skytrax_layers(&course.layers);
+                builder.add_walls(&course.walls);
+                builder.add_pillars(&course.pillars);
+                builder.add_rails(&course.rails);
+                builder.add_connectors(&course.connectors);
+            }
+        }
+
+        (builder.scene, builder.errors)
+    }
+}
+
+#[derive(Default)]
+struct SceneBuilder {
+    scene: Scene,
+    errors: Vec<Error>,
+}
+
+impl SceneBuilder {
+    fn retainer(&self, retainer_id: i32, element: CourseElement) -> Result<&Retainer, Error> {
+        self.scene
+            .retainer(retainer_id)
+            .context(UnknownRetainerSnafu {
+                retainer_id,
+                element,
+            })
+    }
+
+    fn local_to_world_hex_vector(
+        &self,
+        local_hex_vector: &HexVector,
+        retainer_id: i32,
+        element: CourseElement,
+    ) -> Result<HexVector, Error> {
+        let retainer = &self.retainer(retainer_id, element)?.position;
+        Ok(retainer.add(local_hex_vector))
+    }
+
+    fn retainer_height(
+        &self,
+        retainer_id: i32,
+        element: CourseElement,
+    ) -> Result<RetainerHeight, Error> {
+        self.retainer(retainer_id, element)?
+            .height
+            .context(UnknownRetainerHeightSnafu {
+                retainer_id,
+                element,
+            })
+    }
+
+    fn add_retainer(
+        &mut self,
+        id: i32,
+        kind: RetainerKind,
+        position: HexVector,
+        height: Option<RetainerHeight>,
+    ) {
+        self.scene.retainers.insert(
+            id,
+            Retainer {
+                id,
+                kind,
+                position,
+                height,
+            },
+        );
+    }
+
+    fn add_layer(&mut self, id: i32, kind: LayerKind, position: HexVector, height: RetainerHeight) {
+        trace!(
+            "Adding layer id [{id}] of kind [{kind:?}] and height [{height:?}] at position [{position:?}]"
+        );
+        // The positions at this level are already absolute ones
+        self.add_retainer(
+            id,
+            RetainerKind::Layer(kind.clone()),
+            position.clone(),
+            Some(height),
+        );
+        self.scene.layers.push(Layer {
+            id,
+            kind,
+            position,
+            height,
+        });
+    }
+
+    /// Layer heights of the formats before SkyTrax are stored as floats in multiples of 0.36.
+    fn float_layer_height(layer_height: f32) -> RetainerHeight {
+        let lower = (layer_height / TILE_HEIGHT).round() as i32;
+        RetainerHeight::new(lower, lower + 1)
+    }
+
+    fn add_zipline_layers(&mut self, layers: &[ZiplineLayerConstructionData]) {
+        for layer in layers {
+            let height = Self::float_layer_height(layer.layer_height);
+            self.add_layer(
+                layer.layer_id,
+                layer.layer_kind.clone(),
+                layer.hex_vector.clone(),
+                height,
+            );
+
+            for cell in &layer.cell_construction_data {
+                let tiles = Self::stack_zipline_tiles(cell, height.upper);
+                let position = layer.hex_vector.add(&cell.local_hex_position);
+                self.scene.cells.push(Cell {
+                    retainer_id: layer.layer_id,
+                    position,
+                    tiles,
+                });
+            }
+        }
+    }
+
+    /// These formats list the pieces of a cell from the bottom up, stackers included.
+    /// The stackers below a tile become its height like in the later formats, so they are
+    /// counted the same way.
+    fn stack_zipline_tiles(cell: &ZiplineCellConstructionData, base_height: i32) -> Vec<Tile> {
+        let mut tiles: Vec<Tile> = Vec::new();
+        let mut current_height = base_height;
+        let mut stackers = Vec::new();
+        for kind in &cell.tile_kinds {
+            if matches!(kind, TileKind::Stacker | TileKind::StackerSmall) {
+                stackers.push(kind);
+                continue;
+            }
+            let small_stacker = stackers
+                .drain(..)
+                .map(|stacker| catalogue::tile(stacker).height)
+                .sum::<i32>();
+            let parent = tiles.len().checked_sub(1);
+            tiles.push(Tile {
+                kind: kind.clone(),
+                hex_rotation: cell.hex_rotation,
+                parent,
+                base_height: current_height,
+                height_in_small_stacker: Some(small_stacker),
+                on_light_base: false,
+                retainer_id: None,
+                power_signal_mode: None,
+                light_stone_color_mode: None,
+            });
+            current_height += small_stacker + catalogue::tile(kind).height;
+        }
+
+        // Nothing is built on these, keep them as they are
+        for kind in stackers {
+            tiles.push(Tile {
+                kind: kind.clone(),
+                hex_rotation: cell.hex_rotation,
+                parent: tiles.len().checked_sub(1),
+                base_height: current_height,
+                height_in_small_stacker: None,
+                on_light_base: false,
+                retainer_id: None,
+                power_signal_mode: None,
+                light_stone_color_mode: None,
+            });
+            current_height += catalogue::tile(kind).height;
+        }
+        tiles
+    }
+
+    fn add_layers(&mut self, layers: &[LayerConstructionData]) {
+        for layer in layers {
+            let height = Self::float_layer_height(layer.layer_height);
+            self.add_layer(
+                layer.layer_id,
+                layer.layer_kind.clone(),
+                layer.world_hex_position.clone(),
+                height,
+            );
+            for cell in &layer.cell_construction_datas {
+                self.add_cell(
+                    cell,
+                    layer.layer_id,
+                    &layer.world_hex_position,
+                    height.upper,
+                );
+            }
+        }
+    }
+
+    fn add_skytrax_layers(&mut self, layers: &[skytrax::Layer]) {
+        for layer in layers {
+            // SkyTrax stores the height directly as a small-stacker count rather than a float.
+            // Resolve the layer to the same heights the float-based older formats produce.
+            // There, `round(layer_height / TILE_HEIGHT)` gives a base plate a lower edge of -1
+            // and a top of 0 (its height is roughly -0.36), while an elevated layer stores its
+            // lower edge and is built on one small stacker above. SkyTrax stores integer
+            // small-stacker counts: a base plate stores its top (0), every other layer its lower
+            // edge.
+            let stored = layer.small_stacker_height;
+            let height = if layer.layer_kind == LayerKind::BaseLayerPiece {
+                RetainerHeight::new(stored - 1, stored)
+            } else {
+                RetainerHeight::new(stored, stored + 1)
+            };
+            self.add_layer(
+                layer.layer_id,
+                layer.layer_kind.clone(),
+                layer.position.clone(),
+                height,
+            );
+            for cell in &layer.cells {
+                self.add_cell(cell, layer.layer_id, &layer.position, height.upper);
+            }
+        }
+    }
+
+    /// Adds a cell on a layer at the given `base_height`.
+    fn add_cell(
+        &mut self,
+        cell: &CellConstructionData,
+        layer_id: i32,
+        layer_position: &HexVector,
+        base_height: i32,
+    ) {
+        // Convert from local to world position as early as possible
+        let position = layer_position.add(&cell.local_hex_position);
+        self.add_cell_at(&cell.tree_node_data, layer_id, position, base_height);
+    }
+
+    fn add_cell_at(
+        &mut self,
+        tree: &TileTowerTreeNodeData,
+        retainer_id: i32,
+        position: HexVector,
+        base_height: i32,
+    ) {
+        let mut tiles = Vec::new();
+        self.add_tile(tree, &position, None, base_height, false, &mut tiles);
+        self.scene.cells.push(Cell {
+            retainer_id,
+            position,
+            tiles,
+        });
+    }
+
+    fn add_tile(
+        &mut self,
+        node: &TileTowerTreeNodeData,
+        // Each [`TileTowerTreeNodeData`] object belongs to one and only one cell on the board
+        position: &HexVector,
+        parent: Option<usize>,
+        mut current_height: i32,
+        // True when this tile sits directly on a light base, so the stackers
+        // supporting it are lit (formats that stack the light column in the tile
+        // tree rather than as a pillar).
+        on_light_base: bool,
+        tiles: &mut Vec<Tile>,
+    ) {
+        let data = &node.construction_data;
+        let index = tiles.len();
+        tiles.push(Tile {
+            kind: data.kind.clone(),
+            hex_rotation: data.hex_rotation,
+            parent,
+            base_height: current_height,
+            height_in_small_stacker: Some(data.height_in_small_stacker),
+            on_light_base,
+            retainer_id: data.retainer_id,
+            power_signal_mode: data.power_signal_mode,
+            light_stone_color_mode: data.light_stone_color_mode,
+        });
+
+        // If this tile is a retainer (a stacker tower, light base, or double
+        // balcony), record its world position and height so things built on it
+        // resolve correctly.
+        if let Some(retainer_id) = data.retainer_id {
+            // E.g. a stacker tower is 14 small stackers tall and anything built on top of it
+            // starts 14 small stackers higher.
+            let spec = catalogue::tile(&data.kind);
+            let bottom = current_height + data.height_in_small_stacker;
+            let retainer_height = RetainerHeight::new(current_height, bottom + spec.height);
+            let build_height = bottom + spec.retainer.unwrap_or(RetainerSpec::UNKNOWN).build_height;
+            self.add_retainer(
+                retainer_id,
+                RetainerKind::Tile(data.kind.clone()),
+                position.clone(),
+                Some(retainer_height),
+            );
+            current_height = build_height;
+        }
+
+        let children_on_light_base = matches!(data.kind, TileKind::LightBase);
+        for child in &node.children {
+            self.add_tile(
+                child,
+                position,
+                Some(index),
+                current_height,
+                children_on_light_base,
+                tiles,
+            );
+        }
+    }
+
+    fn add_pillars(&mut self, pillars: &[PillarConstructionData]) {
+        for (index, pillar) in pillars.iter().enumerate() {
+            if let Err(error) = self.add_pillar(index, pillar) {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn add_pillar(&mut self, index: usize, pillar: &PillarConstructionData) -> Result<(), Error> {
+        let element = CourseElement::Pillar(index);
+        let lower_height = self.retainer_height(pillar.lower_layer_id, element)?;
+        let upper_height = self.retainer_height(pillar.upper_layer_id, element)?;
+
+        // A pillar fills the gap between the top of the lower retainer and the
+        // lower edge of the upper layer, so the number of small stackers it
+        // needs is `upper.lower - lower.upper`. A base plate has a lower edge of
+        // -1 and a top of 0, and a stacker tower is 14 small stackers (seven
+        // large). Example: an upper layer at height 19 sitting on a stacker
+        // tower with 3 small stackers on it needs 19 - 14 - 3 = 2 more small
+        // stackers, i.e. one large stacker.
+        let small_stacker = upper_height.lower - lower_height.upper;
+        trace!(
+            "Pillar data: {} ({:?}) -> {} ({:?}): {}",
+            pillar.lower_layer_id, lower_height, pillar.upper_layer_id, upper_height, small_stacker
+        );
+
+        let lower = self.retainer(pillar.lower_layer_id, element)?;
+        let pillar = Pillar {
+            index,
+            lower_retainer_id: pillar.lower_layer_id,
+            upper_layer_id: pillar.upper_layer_id,
+            position: lower.position.add(&pillar.lower_cell_local_position),
+            base_height: lower_height.upper,
+            height_in_small_stacker: small_stacker,
+            on_light_base: lower.kind == RetainerKind::Tile(TileKind::LightBase),
+        };
+        self.scene.pillars.push(pillar);
+        Ok(())
+    }
+
+    fn add_walls(&mut self, walls: &[WallConstructionData]) {
+        for (index, wall) in walls.iter().enumerate() {
+            if let Err(error) = self.add_wall(index, wall) {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn add_wall(&mut self, index: usize, wall: &WallConstructionData) -> Result<(), Error> {
+        let element = CourseElement::Wall(index);
+        // Convert all local coordinates to world coordinates as soon as possible
+        let tower_1_position = self.local_to_world_hex_vector(
+            &wall.lower_stacker_tower_1_local_hex_pos,
+            wall.lower_stacker_tower_1_retainer_id,
+            element,
+        )?;
+        let tower_2_position = self.local_to_world_hex_vector(
+            &wall.lower_stacker_tower_2_local_hex_pos,
+            wall.lower_stacker_tower_2_retainer_id,
+            element,
+        )?;
+        let direction = tower_1_position
+            .direction_to(&tower_2_position)
+            .context(WallNotStraightSnafu { element })?;
+        trace!("Wall:\n{:#?}\nWall direction: {:?}", wall, direction);
+
+        // Both towers should stand at the same height, if they don't the wall rests on the
+        // higher one
+        let bottom = self
+            .wall_tower_bottom(
+                wall.lower_stacker_tower_1_retainer_id,
+                &tower_1_position,
+                element,
+            )?
+            .max(self.wall_tower_bottom(
+                wall.lower_stacker_tower_2_retainer_id,
+                &tower_2_position,
+                element,
+            )?);
+
+        // Process balconies as they can all be retainers and we need to know the exact
+        // positions of each of those balconies for proper length calculations of rails
+        let mut balconies = Vec::new();
+        for balcony in &wall.balcony_construction_datas {
+            // This walks a number of cells in the direction of the wall based on the column
+            // in which this particular balcony resides.
+            // Column 0 means that we "stay" at the starting field.
+            let hex_vector =
+                tower_1_position.hex_vector_in_distance(&direction, balcony.wall_coordinate.column);
+
+            // From the wall side and the wall direction we need to get the global direction
+            // of where the balcony will end up in.
+            // When we have that we will get the target cell by finding the proper neighbor
+            // to the wall cell we found above.
+            let target_direction = wall_side_direction(&direction, &balcony.wall_side);
+            let position = hex_vector.neighbor(&target_direction);
+
+            // Like a double balcony a balcony is one small stacker thick and holds its tiles at
+            // its own height
+            let height = bottom + balcony.wall_coordinate.row * WALL_ROW_HEIGHT;
+            self.add_retainer(
+                balcony.retainer_id,
+                RetainerKind::Balcony,
+                position.clone(),
+                Some(RetainerHeight::new(height, height + 1)),
+            );
+
+            // Now process everything that is built on top of a balcony (which will require
+            // the proper retainer location we calculated above)
+            if let Some(cell) = &balcony.cell_construction_datas {
+                self.add_cell_at(
+                    &cell.tree_node_data,
+                    balcony.retainer_id,
+                    position.clone(),
+                    height,
+                );
+            }
+
+            balconies.push(Balcony {
+                retainer_id: balcony.retainer_id,
+                side: balcony.wall_side,
+                coordinate: balcony.wall_coordinate.clone(),
+                position,
+                facing: target_direction,
+            });
+        }
+
+        self.scene.walls.push(Wall {
+            index,
+            tower_1_retainer_id: wall.lower_stacker_tower_1_retainer_id,
+            tower_1_position,
+            tower_2_retainer_id: wall.lower_stacker_tower_2_retainer_id,
+            tower_2_position,
+            direction,
+            bottom,
+            balconies,
+        });
+        Ok(())
+    }
+
+    /// The height the stacker tower at `position` on retainer `retainer_id` stands at, which is
+    /// where the wall it carries starts.
+    /// Falls back to the top of the retainer if there is no stacker tower in that cell.
+    fn wall_tower_bottom(
+        &self,
+        retainer_id: i32,
+        position: &HexVector,
+        element: CourseElement,
+    ) -> Result<i32, Error> {
+        let tower = self
+            .scene
+            .cells
+            .iter()
+            .filter(|cell| {
+                cell.retainer_id == retainer_id
+                    && cell.position.x == position.x
+                    && cell.position.y == position.y
+            })
+            .flat_map(|cell| &cell.tiles)
+            .find(|tile| {
+                matches!(
+                    tile.kind,
+                    TileKind::StackerTowerClosed | TileKind::StackerTowerOpened
+                )
+            });
+        match tower {
+            Some(tower) => Ok(tower.height()),
+            None => Ok(self.retainer_height(retainer_id, element)?.upper),
+        }
+    }
+
+    /// Connectors are resolved last, they only refer to the cells around them.
+    fn add_connectors(&mut self, connectors: &[skytrax::Connector]) {
+        for (index, connector) in connectors.iter().enumerate() {
+            // The position is stored in the same axis order as a `HexVector`
+            let vertex = VertexVector {
+                y: connector.pos_x,
+                x: connector.pos_y,
+            };
+            let joins = self.tiles_at_vertex(&vertex, connector.height);
+            self.scene.connectors.push(Connector {
+                index,
+                vertex,
+                height: connector.height,
+                joins,
+            });
+        }
+    }
+
+    /// The tiles in the cells around `vertex` that reach `height`, a slope for example spans
+    /// from the height it sits at to its top.
+    fn tiles_at_vertex(&self, vertex: &VertexVector, height: i32) -> Vec<TileRef> {
+        let Some(positions) = vertex.cells() else {
+            return Vec::new();
+        };
+        let mut joins = Vec::new();
+        for (cell_index, cell) in self.scene.cells.iter().enumerate() {
+            if !positions
+                .iter()
+                .any(|position| position.x == cell.position.x && position.y == cell.position.y)
+            {
+                continue;
+            }
+            for (tile_index, tile) in cell.tiles.iter().enumerate() {
+                let spec = catalogue::tile(&tile.kind);
+                if !spec.is_closed()
+                    && (tile.height()..=tile.height() + spec.height).contains(&height)
+                {
+                    joins.push(TileRef {
+                        cell: cell_index,
+                        tile: tile_index,
+                    });
+                }
+            }
+        }
+        joins
+    }
+
+    fn add_ropes(&mut self, ropes: &[RopeConstructionData]) {
+        for (index, rope) in ropes.iter().enumerate() {
+            if let Err(error) = self.add_rope(index, rope) {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn add_rope(&mut self, index: usize, rope: &RopeConstructionData) -> Result<(), Error> {
+        let element = CourseElement::Rope(index);
+        let start = self.rope_end(
+            rope.start_tile_layer_index,
+            &rope.start_tile_local_hex_pos,
+            element,
+        )?;
+        let end = self.rope_end(
+            rope.end_tile_layer_index,
+            &rope.end_tile_local_hex_pos,
+            element,
+        )?;
+        self.scene.ropes.push(Rope {
+            index,
+            kind: rope.rope_kind.clone(),
+            start,
+            end,
+        });
+        Ok(())
+    }
+
+    fn rope_end(
+        &self,
+        layer_index: u32,
+        local_position: &HexVector,
+        element: CourseElement,
+    ) -> Result<RopeEnd, Error> {
+        let layer =
+            self.scene
+                .layers
+                .get(layer_index as usize)
+                .context(UnknownLayerIndexSnafu {
+                    layer_index,
+                    element,
+                })?;
+        Ok(RopeEnd {
+            retainer_id: layer.id,
+            position: layer.position.add(local_position),
+        })
+    }
+
+    fn add_rails(&mut self, rails: &[RailConstructionData]) {
+        for (index, rail) in rails.iter().enumerate() {
+            if let Err(error) = self.add_rail(index, rail) {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn add_rail(&mut self, index: usize, rail: &RailConstructionData) -> Result<(), Error> {
+        let element = CourseElement::Rail(index);
+        let rail = Rail {
+            index,
+            kind: rail.rail_kind.clone(),
+            exit_1: self.rail_exit(&rail.exit_1_identifier, element)?,
+            exit_2: self.rail_exit(&rail.exit_2_identifier, element)?,
+            // The course files sometimes contain _a lot_ of additional rails for some
+            // reason, but they are set to "materialized = false", I don't know why.
+            // Example course: 4NPZ3WLJQF
+            materialized: rail.materialized.unwrap_or(true),
+        };
+        self.scene.rails.push(rail);
+        Ok(())
+    }
+
+    fn rail_exit(
+        &self,
+        exit: &RailConstructionExitIdentifier,
+        element: CourseElement,
+    ) -> Result<RailExit, Error> {
+        // A rail has two ends/exits, both are located on a retainer (e.g. a layer)
+        Ok(RailExit {
+            retainer_id: exit.retainer_id,
+            position: self.local_to_world_hex_vector(
+                &exit.cell_local_hex_pos,
+                exit.retainer_id,
+                element,
+            )?,
+            side_hex_rotation: exit.side_hex_rot,
+            local_height: exit.exit_local_pos_y,
+        })
+    }
+}
+
+/// Returns the absolute direction a wall side is facing for a specific wall direction.
+///
+/// Walls in the App have a start and an end point.
+/// By connecting the two you get a direction in which the wall is going.
+/// Balconies can be attached to either side of the wall and the sides are referred to as east and west
+/// which is relative to the direction of the wall and not absolute in relation to the whole board.
+///
+/// To calculate distances between elements on the board we need to know the absolute direction a balcony is facing.
+/// This methods takes care of calculating that direction.
+fn wall_side_direction(direction: &Direction, wall_side: &WallSide) -> Direction {
+    match (direction, wall_side) {
+        (Direction::NorthEast, WallSide::East) => Direction::East,
+        (Direction::NorthEast, WallSide::West) => Direction::NorthWest,
+        (Direction::East, WallSide::East) => Direction::SouthEast,
+        (Direction::East, WallSide::West) => Direction::NorthEast,
+        (Direction::SouthEast, WallSide::East) => Direction::SouthWest,
+        (Direction::SouthEast, WallSide::West) => Direction::East,
+        (Direction::SouthWest, WallSide::East) => Direction::West,
+        (Direction::SouthWest, WallSide::West) => Direction::SouthEast,
+        (Direction::West, WallSide::East) => Direction::NorthWest,
+        (Direction::West, WallSide::West) => Direction::SouthWest,
+        (Direction::NorthWest, WallSide::East) => Direction::NorthEast,
+        (Direction::NorthWest, WallSide::West) => Direction::West,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CourseElement, Error, RetainerHeight, RetainerKind, Scene, TileRef};
+    use crate::app::BillOfMaterials;
+    use crate::app::course::{Corner, Course, Direction, HexVector, SavedCourse, VertexVector};
+    use crate::app::fixtures::{skytrax_course, tower_course, tower_scene, zipline_course};
+    use crate::app::layer::{LayerKind, TileKind};
+    use crate::app::wall::WallSide;
+    use crate::app::ziplineadded2019::RopeKind;
+
+    #[test]
+    fn layers_and_tiles_are_resolved() {
+        let scene = tower_scene();
+
+        assert_eq!(scene.layers.len(), 2);
+        assert_eq!(scene.layers[0].kind, LayerKind::BaseLayerPiece);
+        assert_eq!(scene.layers[0].height, RetainerHeight::new(-1, 0));
+        assert_eq!(scene.layers[1].kind, LayerKind::LargeLayer);
+        assert_eq!(scene.layers[1].height, RetainerHeight::new(19, 20));
+
+        let cell = &scene.cells[0];
+        assert_eq!(cell.retainer_id, 0);
+        assert_eq!((cell.position.y, cell.position.x), (3, 3));
+        let [tower, curve] = cell.tiles.as_slice() else {
+            panic!("expected two tiles, got {:?}", cell.tiles);
+        };
+        assert_eq!(tower.kind, TileKind::StackerTowerClosed);
+        assert_eq!(tower.parent, None);
+        assert_eq!(tower.retainer_id, Some(100));
+        assert_eq!(tower.height(), 2);
+        assert_eq!(curve.kind, TileKind::Curve);
+        assert_eq!(curve.parent, Some(0));
+        assert_eq!(curve.base_height, 16);
+        assert_eq!(curve.height(), 17);
+    }
+
+    #[test]
+    fn pillars_rails_and_walls_are_resolved() {
+        let scene = tower_scene();
+
+        let pillar = &scene.pillars[0];
+        assert_eq!((pillar.position.y, pillar.position.x), (3, 3));
+        assert_eq!(pillar.base_height, 16);
+        assert_eq!(pillar.height_in_small_stacker, 3);
+        assert!(!pillar.on_light_base);
+
+        let rail = &scene.rails[0];
+        assert_eq!((rail.exit_2.position.y, rail.exit_2.position.x), (3, 5));
+        assert_eq!(rail.exit_2.local_height, Some(0.0));
+        assert!(rail.materialized);
+        assert_eq!(rail.span(), 1);
+
+        let wall = &scene.walls[0];
+        assert_eq!(wall.direction, Direction::East);
+        assert_eq!(wall.length(), 2);
+        let balcony = &wall.balconies[0];
+        assert_eq!(balcony.side, WallSide::East);
+        assert_eq!((balcony.position.y, balcony.position.x), (1, 2));
+
+        let balcony_cell = &scene.cells[1];
+        assert_eq!(balcony_cell.retainer_id, 200);
+        assert_eq!(balcony_cell.tiles[0].kind, TileKind::Starter);
+    }
+
+    #[test]
+    fn retainers_are_resolved() {
+        let scene = tower_scene();
+
+        let ids: Vec<_> = scene.retainers.keys().copied().collect();
+        assert_eq!(ids, [0, 1, 100, 200]);
+
+        let tower = scene.retainer(100).unwrap();
+        assert_eq!(tower.kind, RetainerKind::Tile(TileKind::StackerTowerClosed));
+        assert_eq!((tower.position.y, tower.position.x), (3, 3));
+        assert_eq!(tower.height, Some(RetainerHeight::new(0, 16)));
+
+        let layer = scene.retainer(1).unwrap();
+        assert_eq!(layer.kind, RetainerKind::Layer(LayerKind::LargeLayer));
+        assert_eq!(layer.height, Some(RetainerHeight::new(19, 20)));
+
+        let balcony = scene.retainer(200).unwrap();
+        assert_eq!(balcony.kind, RetainerKind::Balcony);
+        assert_eq!((balcony.position.y, balcony.position.x), (1, 2));
+        // There is no stacker tower below the wall, so it stands on the base layer
+        assert_eq!(balcony.height, Some(RetainerHeight::new(0, 1)));
+    }
+
+    #[test]
+    fn balconies_are_as_high_as_their_row() {
+        let mut course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+        let Course::LightStones2023(inner) = &mut course else {
+            panic!("expected a LightStones2023 course");
+        };
+        // Put the wall on the stacker tower, which stands on two small stackers, and move the
+        // balcony up three rows
+        let wall = &mut inner.wall_construction_data[0];
+        wall.lower_stacker_tower_1_local_hex_pos.y = 1;
+        wall.balcony_construction_datas[0].wall_coordinate.row = 3;
+        inner.pillar_construction_data[0].lower_layer_id = 200;
+
+        let scene = Scene::try_from(&course).unwrap();
+        let balcony = scene.retainer(200).unwrap();
+        assert_eq!(balcony.height, Some(RetainerHeight::new(8, 9)));
+        assert_eq!(scene.cells[1].tiles[0].height(), 8);
+        // The pillar from the balcony to the large layer fills the gap between 9 and 19
+        assert_eq!(scene.pillars[0].height_in_small_stacker, 10);
+    }
+
+    #[test]
+    fn unknown_retainers_are_reported() {
+        let mut course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+        let Course::LightStones2023(inner) = &mut course else {
+            panic!("expected a LightStones2023 course");
+        };
+        inner.rail_construction_data[0]
+            .exit_2_identifier
+            .retainer_id = 7;
+
+        let error = Scene::try_from(&course).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::UnknownRetainerError {
+                retainer_id: 7,
+                element: CourseElement::Rail(0)
+            }
+        ));
+        assert_eq!(error.to_string(), "Rail [0] refers to unknown retainer [7]");
+    }
+
+    #[test]
+    fn pillars_on_unknown_retainers_are_reported() {
+        let mut course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+        let Course::LightStones2023(inner) = &mut course else {
+            panic!("expected a LightStones2023 course");
+        };
+        inner.pillar_construction_data[0].lower_layer_id = 300;
+
+        let error = Scene::try_from(&course).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::UnknownRetainerError {
+                retainer_id: 300,
+                element: CourseElement::Pillar(0)
+            }
+        ));
+    }
+
+    #[test]
+    fn zipline_stackers_become_tile_heights() {
+        let mut course = SavedCourse::from_bytes(&zipline_course().with_checksum())
+            .unwrap()
+            .course;
+        let Course::ZiplineAdded2019(inner) = &mut course else {
+            panic!("expected a ZiplineAdded2019 course");
+        };
+        inner.layer_construction_data[0].cell_construction_data[0].tile_kinds = vec![
+            TileKind::Stacker,
+            TileKind::StackerSmall,
+            TileKind::ZiplineStart,
+            TileKind::Stacker,
+            TileKind::Curve,
+            TileKind::StackerSmall,
+        ];
+
+        let scene = Scene::try_from(&course).unwrap();
+        let base = scene.layers[0].height.upper;
+        let [zipline, curve, stacker] = scene.cells[0].tiles.as_slice() else {
+            panic!("expected three tiles, got {:?}", scene.cells[0].tiles);
+        };
+        assert_eq!(zipline.kind, TileKind::ZiplineStart);
+        assert_eq!(zipline.height_in_small_stacker, Some(3));
+        assert_eq!(zipline.height(), base + 3);
+        assert_eq!(curve.parent, Some(0));
+        assert_eq!(curve.base_height, base + 4);
+        assert_eq!(curve.height(), base + 6);
+        // The small stacker on top carries nothing
+        assert_eq!(stacker.kind, TileKind::StackerSmall);
+        assert_eq!(stacker.height_in_small_stacker, None);
+
+        let bom = BillOfMaterials::from(&scene);
+        assert_eq!(bom.tile_kind(TileKind::Stacker), Some(2));
+        assert_eq!(bom.tile_kind(TileKind::StackerSmall), Some(2));
+    }
+
+    #[test]
+    fn zipline_ropes_are_resolved() {
+        let mut course = SavedCourse::from_bytes(&zipline_course().with_checksum())
+            .unwrap()
+            .course;
+        let scene = Scene::try_from(&course).unwrap();
+        let rope = &scene.ropes[0];
+        assert_eq!(rope.kind, RopeKind::Straight);
+        assert_eq!(rope.start.retainer_id, 0);
+        assert_eq!((rope.start.position.y, rope.start.position.x), (1, 2));
+        assert_eq!((rope.end.position.y, rope.end.position.x), (5, 5));
+        // Three steps north west and four west, the fields between both ends
+        assert_eq!(rope.span(), 6);
+
+        let Course::ZiplineAdded2019(inner) = &mut course else {
+            panic!("expected a ZiplineAdded2019 course");
+        };
+        inner.rope_construction_data[0].end_tile_layer_index = 3;
+        let error = Scene::try_from(&course).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Rope [0] refers to unknown layer number [3]"
+        );
+    }
+
+    #[test]
+    fn connectors_join_the_tiles_around_them() {
+        let mut course = SavedCourse::from_bytes(&skytrax_course().with_checksum())
+            .unwrap()
+            .course;
+        let Course::SkyTrax(inner) = &mut course else {
+            panic!("expected a SkyTrax course");
+        };
+        // The starter sits on a light base at 7 in the cell at (-2, 6)
+        let vertex = VertexVector::new(&HexVector::new(6, -2), Corner::South);
+        inner.connectors[0].pos_x = vertex.y;
+        inner.connectors[0].pos_y = vertex.x;
+        inner.connectors[0].height = 8;
+
+        // The pillar of this course is on a layer that doesn't exist
+        let (scene, _) = Scene::resolve(&course);
+        let [connector, not_on_vertex] = scene.connectors.as_slice() else {
+            panic!("expected two connectors, got {:?}", scene.connectors);
+        };
+        assert_eq!(connector.vertex, vertex);
+        assert_eq!(connector.joins, [TileRef { cell: 0, tile: 1 }]);
+        assert_eq!(scene.cells[0].tiles[1].kind, TileKind::Starter);
+        assert!(not_on_vertex.joins.is_empty());
+    }
+}
@@ -4,6 +4,11 @@
 //! array that earlier formats do not have, and a layer stores its height as an
 //! integer count of small stackers rather than a float. The rail, pillar, wall,
 //! cell, and tile-tower structures are shared with the earlier format.
+//!
+//! Version 6 (`PreSkyTraxDuringDevelopment`) was written by beta releases of
+//! the app while SkyTrax was being developed. It is read with the same layout,
+//! which is an assumption that has not been checked against a real beta course
+//! yet (see [`Course::PreSkyTrax`](crate::app::course::Course::PreSkyTrax)).
 
 use deku::prelude::*;
 use serde::Serialize;
@@ -12,41 +17,43 @@ use crate::app::course::{
     CourseElementGeneration, CourseMetaData, CourseSaveDataVersion, HexVector,
 };
 use crate::app::layer::{CellConstructionData, LayerKind};
+use crate::app::layout::Layout;
 use crate::app::pillar::PillarConstructionData;
 use crate::app::rail::RailConstructionData;
 use crate::app::wall::WallConstructionData;
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct Course {
     pub meta_data: CourseMetaData,
     pub generation: CourseElementGeneration,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "layers.len() as i32")]
     layer_count: i32,
     #[deku(ctx = "version")]
     #[deku(count = "layer_count")]
     pub layers: Vec<Layer>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "rails.len() as i32")]
     rail_count: i32,
     #[deku(ctx = "version")]
     #[deku(count = "rail_count")]
     pub rails: Vec<RailConstructionData>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "pillars.len() as i32")]
     pillar_count: i32,
     #[deku(count = "pillar_count")]
     pub pillars: Vec<PillarConstructionData>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "walls.len() as i32")]
     wall_count: i32,
     #[deku(ctx = "version")]
     #[deku(count = "wall_count")]
     pub walls: Vec<WallConstructionData>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "connectors.len() as i32")]
     connector_count: i32,
     #[deku(count = "connector_count")]
     pub connectors: Vec<Connector>,
@@ -54,7 +61,8 @@ pub struct Course {
 
 /// A layer in a SkyTrax course. The height is an integer count of small
 /// stackers, where earlier formats store a float layer height.
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct Layer {
@@ -69,7 +77,7 @@ pub struct Layer {
     pub position: HexVector,
     pub small_stacker_height: i32,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "cells.len() as i32")]
     cell_count: i32,
     #[deku(ctx = "version")]
     #[deku(count = "cell_count")]
@@ -77,7 +85,7 @@ pub struct Layer {
 }
 
 /// A connector joins two cells at a shared hex vertex.
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Clone, Debug, DekuRead, DekuWrite, Layout, Serialize)]
 pub struct Connector {
     pub pos_x: i32,
     pub pos_y: i32,
@@ -87,6 +95,8 @@ pub struct Connector {
 #[cfg(test)]
 mod tests {
     use crate::app::course::{Course, SavedCourse};
+    use crate::app::fixtures::RawCourse;
+    use crate::app::layer::TileKind;
     use std::path::PathBuf;
 
     /// A real SkyTrax course parses end to end: only the trailing sha256 is
@@ -131,7 +141,7 @@ mod tests {
         let bytes = std::fs::read(&path).expect("read fixture");
 
         let course = SavedCourse::from_bytes(&bytes).expect("parses");
-        let bom = crate::app::BillOfMaterials::from(course.course);
+        let bom = crate::app::BillOfMaterials::try_from(course.course).unwrap();
 
         assert_eq!(bom.connectors, 10, "connector count");
         assert!(
@@ -139,4 +149,51 @@ mod tests {
             "course has counted tiles"
         );
     }
+
+    /// A version 6 course laid out like a SkyTrax course reads as
+    /// `Course::PreSkyTrax` and shows up in the bill of materials like a
+    /// released one. The bytes are assembled with the SkyTrax layout, so this
+    /// only covers the wiring, not whether beta courses really look like this.
+    #[test]
+    fn pre_skytrax_course_parses_and_produces_bill_of_materials() {
+        let bytes = RawCourse::default()
+            .header(6)
+            .meta_data("Beta")
+            .u32(13) // generation: SkyTrax
+            .i32(1)
+            .i32(0) // layer id
+            .u32(0) // base layer piece
+            .i32(0)
+            .i32(0)
+            .i32(0) // small stacker height
+            .i32(1)
+            .i32(0)
+            .i32(0)
+            .i32(0) // index
+            .i32(0) // children
+            .u32(125) // SkyTrax starter
+            .i32(4)
+            .i32(0)
+            .i32(-2_147_483_647) // no retainer id
+            .u32(2_147_483_648) // no power signal mode
+            .u32(2_147_483_648) // no light stone colour
+            .i32(0) // rails
+            .i32(0) // pillars
+            .i32(0) // walls
+            .i32(1)
+            .i32(1)
+            .i32(2)
+            .i32(4)
+            .with_checksum();
+
+        let course = SavedCourse::from_bytes(&bytes).expect("version 6 parses");
+        assert!(matches!(course.course, Course::PreSkyTrax(_)));
+        assert_eq!(course.to_bytes().unwrap(), bytes);
+
+        let bom = crate::app::BillOfMaterials::try_from(course.course).unwrap();
+        assert_eq!(bom.connectors, 1);
+        assert_eq!(bom.tile_kind(TileKind::KstStarter), Some(1));
+        assert_eq!(bom.tile_kind(TileKind::StackerSmall), None);
+        assert_eq!(bom.tile_kind(TileKind::Stacker), Some(2));
+    }
 }
@@ -0,0 +1,579 @@
+//! Draws courses as SVG images.
+//!
+//! The [`plan`] shows a course from above at a scale of 1:1 (in millimetres), so it can be
+//! printed and used to lay out the base plates. Every layer is a group of its own, they can be
+//! switched on and off by clicking the legend when the image is opened in a browser, and show up
+//! as layers in Inkscape.
+//!
+//! An [`elevation`] shows the vertical structure the plan hides: the course is projected along
+//! one of the hex axes, with its layers, stacker columns split into their small and large (or
+//! light) stackers, the tiles on top of them and the rails at the heights of their exits. Pieces
+//! further away are drawn first, so the closer ones cover them.
+use std::fmt::Write;
+
+use crate::app::bom::stacker_column;
+use crate::app::course::Direction;
+use crate::app::layer::TileKind;
+use crate::app::pose::{CELL_DISTANCE_MM, CELL_RADIUS_MM, Pose, Poses, SMALL_STACKER_HEIGHT_MM};
+use crate::app::scene::{Cell, Scene};
+use crate::catalogue;
+
+/// Space around the drawing.
+const MARGIN_MM: f32 = 10.0;
+/// The height of a line of the legend.
+const LEGEND_LINE_MM: f32 = 6.0;
+
+const STYLE: &str = "\
+.cell { fill: #f4f4f4; stroke: #999; stroke-width: 0.5; }
+.reference { fill: #dde8dd; }
+.tile { fill: #ffffff; stroke: #333; stroke-width: 0.5; }
+.balcony { fill: #f3ead8; stroke: #a0855b; stroke-width: 0.5; }
+.direction { stroke: #c33; stroke-width: 1; }
+.rail { stroke: #3366cc; stroke-width: 2; stroke-linecap: round; }
+.wall { stroke: #a0855b; stroke-width: 4; stroke-linecap: round; }
+.pillar { fill: #c33; }
+.layer { fill: #dde8dd; stroke: #7a9a7a; stroke-width: 0.5; }
+.stacker { fill: #e4e4e4; stroke: #666; stroke-width: 0.3; }
+.stacker.small { fill: #f2f2f2; }
+.stacker.lit { fill: #fff1a8; }
+.retainer { fill: #d8e0ee; }
+.light-base { fill: #ffe066; }
+.wall-side { fill: #f3ead8; stroke: #a0855b; stroke-width: 0.5; }
+text { font-family: sans-serif; font-size: 4px; text-anchor: middle; dominant-baseline: middle; }
+.legend text { font-size: 5px; text-anchor: start; cursor: pointer; }
+";
+
+const SCRIPT: &str = "\
+function toggle(id) {
+  const group = document.getElementById(id);
+  group.style.display = group.style.display === 'none' ? '' : 'none';
+}
+";
+
+/// The course seen from above, see the [module documentation](self).
+pub fn plan(scene: &Scene) -> String {
+    let poses = Poses::from(scene);
+    let mut bounds = Bounds::default();
+    let mut groups = Vec::new();
+
+    for layer in &scene.layers {
+        let mut group = Group::new(
+            format!("layer-{}", layer.id),
+            format!("Layer {} ({:?})", layer.id, layer.kind),
+        );
+        let reference = Pose::at(&layer.position, 0);
+        bounds.add_cell(reference);
+        group.hexagon(reference, 1.0, "cell reference");
+        for cell in scene
+            .cells
+            .iter()
+            .filter(|cell| cell.retainer_id == layer.id)
+        {
+            let pose = Pose::at(&cell.position, 0);
+            bounds.add_cell(pose);
+            group.hexagon(pose, 1.0, "cell");
+            group.tile(cell);
+        }
+        groups.push(group);
+    }
+
+    let mut walls = Group::new("walls".to_string(), "Walls and balconies".to_string());
+    for wall in &scene.walls {
+        let start = Pose::at(&wall.tower_1_position, 0);
+        let end = Pose::at(&wall.tower_2_position, 0);
+        bounds.add_cell(start);
+        bounds.add_cell(end);
+        walls.line(start, end, "wall");
+        for balcony in &wall.balconies {
+            let pose = Pose::at(&balcony.position, 0);
+            bounds.add_cell(pose);
+            walls.hexagon(pose, 0.95, "balcony");
+            match scene
+                .cells
+                .iter()
+                .find(|cell| cell.retainer_id == balcony.retainer_id)
+            {
+                Some(cell) => walls.tile(cell),
+                None => walls.label(pose, "Balcony"),
+            }
+        }
+    }
+    groups.push(walls);
+
+    let mut rails = Group::new("rails".to_string(), "Rails".to_string());
+    for rail in &poses.rails {
+        let [start, end] = rail.exits;
+        rails.line(start, end, "rail");
+    }
+    groups.push(rails);
+
+    let mut pillars = Group::new("pillars".to_string(), "Pillars".to_string());
+    for pillar in &scene.pillars {
+        let pose = Pose::at(&pillar.position, 0);
+        bounds.add_cell(pose);
+        let (x, y) = svg_point(pose);
+        let _ = writeln!(
+            pillars.content,
+            r#"<circle class="pillar" cx="{x:.2}" cy="{y:.2}" r="3"/>"#
+        );
+        let label = Pose {
+            y: pose.y - 6.0,
+            ..pose
+        };
+        pillars.label(label, &format!("Pillar {}", pillar.height_in_small_stacker));
+    }
+    groups.push(pillars);
+
+    document(groups, bounds)
+}
+
+/// The course seen from the side, looking in the direction of `axis`, see the
+/// [module documentation](self).
+pub fn elevation(scene: &Scene, axis: Direction) -> String {
+    let poses = Poses::from(scene);
+    let view = View::along(axis);
+    let mut bounds = Bounds::default();
+    let mut groups = Vec::new();
+
+    let mut layers = Shapes::default();
+    for layer in &poses.layers {
+        let positions = scene
+            .layers
+            .iter()
+            .filter(|l| l.id == layer.id)
+            .map(|l| &l.position)
+            .chain(
+                scene
+                    .cells
+                    .iter()
+                    .filter(|cell| cell.retainer_id == layer.id)
+                    .map(|cell| &cell.position),
+            )
+            .map(|position| Pose::at(position, 0));
+        let (mut left, mut right, mut depth) = (f32::MAX, f32::MIN, f32::MAX);
+        for pose in positions {
+            left = left.min(view.across(pose));
+            right = right.max(view.across(pose));
+            depth = depth.min(view.depth(pose));
+        }
+        let bottom = layer.pose.z;
+        let top = bottom + layer.height.max(1.0);
+        let half = CELL_DISTANCE_MM / 2.0;
+        layers.rect(
+            &mut bounds,
+            depth,
+            [left - half, right + half],
+            [bottom, top],
+            "layer",
+        );
+        layers.label(
+            depth,
+            ((left + right) / 2.0, (bottom + top) / 2.0),
+            &format!("Layer {} ({:?})", layer.id, layer.kind),
+        );
+    }
+    groups.push(layers.into_group("layers", "Layers"));
+
+    let mut stackers = Shapes::default();
+    for column in &poses.columns {
+        let (center, depth) = (view.across(column.pose), view.depth(column.pose));
+        let width = CELL_DISTANCE_MM * 0.35;
+        let mut bottom = column.pose.z;
+        let height = (column.height / SMALL_STACKER_HEIGHT_MM).round() as i32;
+        for kind in stacker_column(height, column.lit) {
+            let (small, class) = match kind {
+                TileKind::StackerSmall => (true, "stacker small"),
+                TileKind::LightStackerSmall => (true, "stacker small lit"),
+                TileKind::LightStacker => (false, "stacker lit"),
+                _ => (false, "stacker"),
+            };
+            let top = bottom + SMALL_STACKER_HEIGHT_MM * if small { 1.0 } else { 2.0 };
+            let across = [center - width, center + width];
+            stackers.rect(&mut bounds, depth, across, [bottom, top], class);
+            bottom = top;
+        }
+    }
+    groups.push(stackers.into_group("stackers", "Stackers"));
+
+    let mut tiles = Shapes::default();
+    for tile in &poses.tiles {
+        let (center, depth) = (view.across(tile.pose), view.depth(tile.pose));
+        let width = CELL_DISTANCE_MM * 0.45;
+        // Some pieces (e.g. stacker batches) have no known height, keep them visible
+        let height = tile.height.max(SMALL_STACKER_HEIGHT_MM / 4.0);
+        let (bottom, top) = (tile.pose.z, tile.pose.z + height);
+        let class = if tile.kind == TileKind::LightBase {
+            "tile light-base"
+        } else if catalogue::tile(&tile.kind).retainer.is_some() {
+            "tile retainer"
+        } else {
+            "tile"
+        };
+        let across = [center - width, center + width];
+        tiles.rect(&mut bounds, depth, across, [bottom, top], class);
+        tiles.label(
+            depth,
+            (center, (bottom + top) / 2.0),
+            &format!("{:?}", tile.kind),
+        );
+    }
+    groups.push(tiles.into_group("tiles", "Tiles"));
+
+    let mut walls = Shapes::default();
+    for (wall, pose) in scene.walls.iter().zip(&poses.walls) {
+        let (start, end) = (pose.pose, pose.pose.forward(pose.length));
+        let (start, end) = (view.across(start), view.across(end));
+        let half = CELL_DISTANCE_MM / 2.0;
+        let across = [start.min(end) - half, start.max(end) + half];
+        let bottom = pose.pose.z;
+        let top = bottom + SMALL_STACKER_HEIGHT_MM * (wall.rows() * 2) as f32;
+        let depth = view
+            .depth(pose.pose)
+            .min(view.depth(pose.pose.forward(pose.length)));
+        walls.rect(&mut bounds, depth, across, [bottom, top], "wall-side");
+    }
+    for balcony in &poses.balconies {
+        let (center, depth) = (view.across(balcony.pose), view.depth(balcony.pose));
+        let width = CELL_DISTANCE_MM * 0.45;
+        let across = [center - width, center + width];
+        let (bottom, top) = (balcony.pose.z, balcony.pose.z + SMALL_STACKER_HEIGHT_MM);
+        walls.rect(&mut bounds, depth, across, [bottom, top], "balcony");
+    }
+    groups.push(walls.into_group("walls", "Walls and balconies"));
+
+    let mut rails = Shapes::default();
+    for rail in &poses.rails {
+        let [start, end] = rail.exits;
+        let (x1, y1) = view.point(start);
+        let (x2, y2) = view.point(end);
+        let depth = view.depth(start).min(view.depth(end));
+        rails.push(
+            depth,
+            format!(r#"<line class="rail" x1="{x1:.2}" y1="{y1:.2}" x2="{x2:.2}" y2="{y2:.2}"/>"#),
+        );
+    }
+    groups.push(rails.into_group("rails", "Rails"));
+
+    document(groups, bounds)
+}
+
+/// Projects poses onto the drawing of an [`elevation`].
+struct View {
+    sin: f32,
+    cos: f32,
+}
+
+impl View {
+    /// Looking in the direction of `axis`.
+    fn along(axis: Direction) -> View {
+        let (sin, cos) = Pose::default().facing(axis).yaw.to_radians().sin_cos();
+        View { sin, cos }
+    }
+
+    /// How far to the right of the viewer a pose is.
+    fn across(&self, pose: Pose) -> f32 {
+        pose.x * self.sin - pose.y * self.cos
+    }
+
+    /// How far away from the viewer a pose is.
+    fn depth(&self, pose: Pose) -> f32 {
+        pose.x * self.cos + pose.y * self.sin
+    }
+
+    /// SVG has y pointing down, so up is up.
+    fn point(&self, pose: Pose) -> (f32, f32) {
+        (self.across(pose), -pose.z)
+    }
+}
+
+/// The shapes of a group of an [`elevation`], with how far away they are.
+#[derive(Default)]
+struct Shapes {
+    shapes: Vec<(f32, String)>,
+}
+
+impl Shapes {
+    fn push(&mut self, depth: f32, shape: String) {
+        self.shapes.push((depth, shape));
+    }
+
+    /// A rectangle between two points `across` and two heights.
+    fn rect(
+        &mut self,
+        bounds: &mut Bounds,
+        depth: f32,
+        across: [f32; 2],
+        heights: [f32; 2],
+        class: &str,
+    ) {
+        let [left, right] = across;
+        let [bottom, top] = heights;
+        bounds.add_point(left, -top);
+        bounds.add_point(right, -bottom);
+        let (width, height) = (right - left, top - bottom);
+        self.push(
+            depth,
+            format!(
+                r#"<rect class="{class}" x="{left:.2}" y="{y:.2}" width="{width:.2}" height="{height:.2}"/>"#,
+                y = -top,
+            ),
+        );
+    }
+
+    /// A label at a point across and a height.
+    fn label(&mut self, depth: f32, (across, height): (f32, f32), text: &str) {
+        self.push(
+            depth,
+            format!(
+                r#"<text x="{across:.2}" y="{y:.2}">{}</text>"#,
+                escape(text),
+                y = -height,
+            ),
+        );
+    }
+
+    /// The group with the shapes furthest away first, shapes at the same distance keep their
+    /// order.
+    fn into_group(mut self, id: &str, label: &str) -> Group {
+        self.shapes.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        let mut group = Group::new(id.to_string(), label.to_string());
+        for (_, shape) in self.shapes {
+            let _ = writeln!(group.content, "{shape}");
+        }
+        group
+    }
+}
+
+/// Writes the groups with a legend above them to switch them on and off.
+fn document(groups: Vec<Group>, mut bounds: Bounds) -> String {
+    // The legend goes above the drawing
+    if bounds.is_empty() {
+        bounds.add_cell(Pose::default());
+    }
+    let legend_top = bounds.min_y - MARGIN_MM - LEGEND_LINE_MM * groups.len() as f32;
+    let min_x = bounds.min_x - MARGIN_MM;
+    let min_y = legend_top - MARGIN_MM;
+    let width = bounds.max_x + MARGIN_MM - min_x;
+    let height = bounds.max_y + MARGIN_MM - min_y;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{width:.2}mm" height="{height:.2}mm" viewBox="{min_x:.2} {min_y:.2} {width:.2} {height:.2}">"#
+    );
+    let _ = writeln!(svg, "<style>\n{STYLE}</style>");
+    let _ = writeln!(svg, "<script>\n{SCRIPT}</script>");
+
+    let _ = writeln!(svg, r#"<g class="legend">"#);
+    for (line, group) in groups.iter().enumerate() {
+        let y = legend_top + LEGEND_LINE_MM * line as f32;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{x:.2}" y="{y:.2}" onclick="toggle('{id}')">{label}</text>"#,
+            x = bounds.min_x,
+            id = group.id,
+            label = escape(&group.label),
+        );
+    }
+    let _ = writeln!(svg, "</g>");
+
+    for group in groups {
+        let _ = writeln!(
+            svg,
+            r#"<g id="{id}" inkscape:groupmode="layer" inkscape:label="{label}">"#,
+            id = group.id,
+            label = escape(&group.label),
+        );
+        svg.push_str(&group.content);
+        let _ = writeln!(svg, "</g>");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// A part of the drawing that can be switched on and off.
+struct Group {
+    id: String,
+    label: String,
+    content: String,
+}
+
+impl Group {
+    fn new(id: String, label: String) -> Group {
+        Group {
+            id,
+            label,
+            content: String::new(),
+        }
+    }
+
+    /// A hexagon around a cell, `radius` is relative to the cell.
+    fn hexagon(&mut self, pose: Pose, radius: f32, class: &str) {
+        let (x, y) = svg_point(pose);
+        let points: Vec<_> = (0..6)
+            .map(|corner| {
+                let (sin, cos) = (30.0f32 + 60.0 * corner as f32).to_radians().sin_cos();
+                let radius = radius * CELL_RADIUS_MM;
+                format!("{:.2},{:.2}", x + cos * radius, y - sin * radius)
+            })
+            .collect();
+        let _ = writeln!(
+            self.content,
+            r#"<polygon class="{class}" points="{}"/>"#,
+            points.join(" ")
+        );
+    }
+
+    fn line(&mut self, start: Pose, end: Pose, class: &str) {
+        let (x1, y1) = svg_point(start);
+        let (x2, y2) = svg_point(end);
+        let _ = writeln!(
+            self.content,
+            r#"<line class="{class}" x1="{x1:.2}" y1="{y1:.2}" x2="{x2:.2}" y2="{y2:.2}"/>"#
+        );
+    }
+
+    fn label(&mut self, pose: Pose, text: &str) {
+        let (x, y) = svg_point(pose);
+        let _ = writeln!(
+            self.content,
+            r#"<text x="{x:.2}" y="{y:.2}">{}</text>"#,
+            escape(text)
+        );
+    }
+
+    /// The topmost tile of a cell, with a mark on the side it is turned towards and its height.
+    fn tile(&mut self, cell: &Cell) {
+        let Some(tile) = cell.top_tile() else {
+            return;
+        };
+        let pose = Pose::at(&cell.position, 0).turned(tile.hex_rotation);
+        self.hexagon(pose, 0.8, "tile");
+        let mark = pose.forward(CELL_RADIUS_MM * 0.75);
+        let inner = pose.forward(CELL_RADIUS_MM * 0.45);
+        self.line(inner, mark, "direction");
+        self.label(pose, &format!("{:?}", tile.kind));
+        let height = Pose {
+            y: pose.y - 6.0,
+            ..pose
+        };
+        self.label(height, &format!("h {}", tile.height()));
+    }
+}
+
+/// The area covered by the drawing, in SVG coordinates.
+#[derive(Default)]
+struct Bounds {
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    points: usize,
+}
+
+impl Bounds {
+    fn is_empty(&self) -> bool {
+        self.points == 0
+    }
+
+    /// Makes room for a cell seen from above.
+    fn add_cell(&mut self, pose: Pose) {
+        let (x, y) = svg_point(pose);
+        self.add_point(x - CELL_RADIUS_MM, y - CELL_RADIUS_MM);
+        self.add_point(x + CELL_RADIUS_MM, y + CELL_RADIUS_MM);
+    }
+
+    fn add_point(&mut self, x: f32, y: f32) {
+        if self.is_empty() {
+            (self.min_x, self.min_y, self.max_x, self.max_y) = (x, y, x, y);
+        } else {
+            self.min_x = self.min_x.min(x);
+            self.min_y = self.min_y.min(y);
+            self.max_x = self.max_x.max(x);
+            self.max_y = self.max_y.max(y);
+        }
+        self.points += 1;
+    }
+}
+
+/// SVG has y pointing down, so north is up.
+fn svg_point(pose: Pose) -> (f32, f32) {
+    (pose.x, -pose.y)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{elevation, escape, plan};
+    use crate::app::course::Direction;
+    use crate::app::fixtures::tower_scene;
+
+    #[test]
+    fn plans_have_a_group_per_layer() {
+        let scene = tower_scene();
+        let svg = plan(&scene);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+        for layer in &scene.layers {
+            let id = format!("layer-{}", layer.id);
+            assert!(svg.contains(&format!(r#"<g id="{id}""#)), "{id}");
+            assert!(svg.contains(&format!("toggle('{id}')")), "{id}");
+        }
+        for id in ["walls", "rails", "pillars"] {
+            assert!(svg.contains(&format!(r#"<g id="{id}""#)), "{id}");
+        }
+
+        // The curve sits on the stacker tower, the starter on the balcony
+        assert!(svg.contains(">Curve</text>"));
+        assert!(svg.contains(">h 17</text>"));
+        assert!(svg.contains(">Starter</text>"));
+        assert_eq!(svg.matches(r#"class="rail""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="wall""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="pillar""#).count(), 1);
+    }
+
+    #[test]
+    fn elevations_show_stacker_columns() {
+        let scene = tower_scene();
+        let svg = elevation(&scene, Direction::East);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+        for id in ["layers", "stackers", "tiles", "walls", "rails"] {
+            assert!(svg.contains(&format!(r#"<g id="{id}""#)), "{id}");
+        }
+
+        // The curve is one small stacker above the stacker tower, the pillar is a column of its
+        // own
+        assert!(svg.contains(">StackerTowerClosed</text>"));
+        assert!(svg.contains(">Curve</text>"));
+        assert!(svg.contains(r#"class="tile retainer""#));
+        assert!(svg.contains(r#"class="stacker small""#));
+        assert_eq!(svg.matches(r#"class="rail""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="wall-side""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="layer""#).count(), scene.layers.len());
+
+        // Looking from the other side mirrors the drawing, the same pieces are there
+        let opposite = elevation(&scene, Direction::West);
+        assert_eq!(
+            opposite.matches("<rect ").count(),
+            svg.matches("<rect ").count()
+        );
+        assert_ne!(opposite, svg);
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
@@ -0,0 +1,448 @@
+//! Structural problems of a course, e.g. rails that are too long or tiles in the same place.
+//!
+//! The app doesn't produce any of these, they show up in corrupted or hand-edited courses and
+//! point to things this library doesn't understand yet.
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use crate::app::course::{Course, Direction, HexVector};
+use crate::app::graph::Graph;
+use crate::app::layer::{LayerKind, TileKind};
+use crate::app::rail::RailKind;
+use crate::app::scene::{self, Rail, RailExit, Scene};
+use crate::app::wall::WallKind;
+use crate::catalogue;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub enum Problem {
+    /// A rail, pillar, wall or rope the scene could not be resolved with, e.g. one on a retainer
+    /// that doesn't exist. It is left out of all other checks.
+    Scene(scene::Error),
+    /// A straight rail spanning more fields than the longest straight rail.
+    RailSpan {
+        rail: usize,
+        span: i32,
+    },
+    /// A wall whose towers are further apart (or closer) than any wall piece.
+    WallLength {
+        wall: usize,
+        length: i32,
+    },
+    UnknownLayerKind {
+        layer_id: i32,
+        kind: u32,
+    },
+    UnknownTileKind {
+        position: HexVector,
+        kind: u32,
+    },
+    /// Two tiles of a cell taking up the same height.
+    OverlappingTiles {
+        position: HexVector,
+        height: i32,
+        tiles: [TileKind; 2],
+    },
+    /// A rail ending in a cell without a tile it could be attached to.
+    RailExitWithoutTile {
+        rail: usize,
+        exit: usize,
+    },
+    /// A rail ending on a side of a tile marbles can't run through.
+    RailExitClosed {
+        rail: usize,
+        exit: usize,
+        tile: TileKind,
+        side: Direction,
+    },
+    /// A SkyTrax connector at a position that is not the corner of a cell.
+    ConnectorNotOnVertex {
+        connector: usize,
+    },
+    /// A SkyTrax connector with less than two tiles to join at its height.
+    LooseConnector {
+        connector: usize,
+        tiles: usize,
+    },
+    /// A rail ending on a side that doesn't exist.
+    InvalidRailSide {
+        rail: usize,
+        exit: usize,
+        side_hex_rotation: i32,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Scene(error) => write!(f, "{error}"),
+            Problem::RailSpan { rail, span } => {
+                write!(f, "Rail [{rail}] is a straight rail spanning {span} fields")
+            }
+            Problem::WallLength { wall, length } => {
+                write!(f, "Wall [{wall}] spans {length} fields")
+            }
+            Problem::UnknownLayerKind { layer_id, kind } => {
+                write!(f, "Layer [{layer_id}] has unknown kind [{kind}]")
+            }
+            Problem::UnknownTileKind { position, kind } => write!(
+                f,
+                "Tile at [{}, {}] has unknown kind [{kind}]",
+                position.y, position.x
+            ),
+            Problem::OverlappingTiles {
+                position,
+                height,
+                tiles: [first, second],
+            } => write!(
+                f,
+                "Tiles [{first:?}] and [{second:?}] at [{}, {}] overlap at height {height}",
+                position.y, position.x
+            ),
+            Problem::RailExitWithoutTile { rail, exit } => {
+                write!(f, "Exit {exit} of rail [{rail}] is not on a tile")
+            }
+            Problem::RailExitClosed {
+                rail,
+                exit,
+                tile,
+                side,
+            } => write!(
+                f,
+                "Exit {exit} of rail [{rail}] is on the closed {side:?} side of [{tile:?}]"
+            ),
+            Problem::ConnectorNotOnVertex { connector } => {
+                write!(f, "Connector [{connector}] is not at the corner of a cell")
+            }
+            Problem::LooseConnector { connector, tiles } => {
+                write!(f, "Connector [{connector}] joins {tiles} tiles")
+            }
+            Problem::InvalidRailSide {
+                rail,
+                exit,
+                side_hex_rotation,
+            } => write!(
+                f,
+                "Exit {exit} of rail [{rail}] is on side [{side_hex_rotation}] which does not exist"
+            ),
+        }
+    }
+}
+
+impl From<&Course> for ValidationReport {
+    fn from(course: &Course) -> Self {
+        let (scene, errors) = Scene::resolve(course);
+        let mut problems: Vec<Problem> = errors.into_iter().map(Problem::Scene).collect();
+
+        for layer in &scene.layers {
+            if let LayerKind::Unknown(kind) = layer.kind {
+                problems.push(Problem::UnknownLayerKind {
+                    layer_id: layer.id,
+                    kind,
+                });
+            }
+        }
+
+        for cell in &scene.cells {
+            for tile in &cell.tiles {
+                if let TileKind::Unknown(kind) = tile.kind {
+                    problems.push(Problem::UnknownTileKind {
+                        position: cell.position.clone(),
+                        kind,
+                    });
+                }
+            }
+            problems.extend(overlapping_tiles(cell));
+        }
+
+        for wall in &scene.walls {
+            let length = wall.length();
+            if WallKind::straight_of_length(length).is_none() {
+                problems.push(Problem::WallLength {
+                    wall: wall.index,
+                    length,
+                });
+            }
+        }
+
+        let graph = Graph::from(&scene);
+        for rail in scene.rails.iter().filter(|rail| rail.materialized) {
+            let span = rail.span();
+            if rail.kind == RailKind::Straight && !(0..=3).contains(&span) {
+                problems.push(Problem::RailSpan {
+                    rail: rail.index,
+                    span,
+                });
+            }
+            for (exit, rail_exit) in [(1, &rail.exit_1), (2, &rail.exit_2)] {
+                problems.extend(rail_exit_problem(&scene, &graph, rail, exit, rail_exit));
+            }
+        }
+
+        for connector in &scene.connectors {
+            if connector.vertex.corner().is_none() {
+                problems.push(Problem::ConnectorNotOnVertex {
+                    connector: connector.index,
+                });
+            } else if connector.joins.len() < 2 {
+                problems.push(Problem::LooseConnector {
+                    connector: connector.index,
+                    tiles: connector.joins.len(),
+                });
+            }
+        }
+
+        ValidationReport { problems }
+    }
+}
+
+/// Tiles (ignoring stackers) whose heights overlap. A tile is not compared with the retainer it
+/// is built on, some retainers (e.g. double balconies) hold their tiles at their own height.
+fn overlapping_tiles(cell: &scene::Cell) -> Vec<Problem> {
+    let tiles: Vec<_> = cell
+        .tiles
+        .iter()
+        .enumerate()
+        .filter(|(_, tile)| tile.height_in_small_stacker.is_some() && !tile.kind.is_stacker())
+        .map(|(index, tile)| {
+            let height = catalogue::tile(&tile.kind).height.max(1);
+            (index, tile, tile.height()..tile.height() + height)
+        })
+        .collect();
+
+    let mut problems = Vec::new();
+    for (position, (first_index, first, first_range)) in tiles.iter().enumerate() {
+        for (second_index, second, second_range) in &tiles[position + 1..] {
+            if first.parent == Some(*second_index) || second.parent == Some(*first_index) {
+                continue;
+            }
+            if first_range.start < second_range.end && second_range.start < first_range.end {
+                problems.push(Problem::OverlappingTiles {
+                    position: cell.position.clone(),
+                    height: first_range.start.max(second_range.start),
+                    tiles: [first.kind.clone(), second.kind.clone()],
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// Checks that a rail exit is on a tile that is open on that side.
+/// The side of an exit is stored in world rotation, not relative to the tile.
+fn rail_exit_problem(
+    scene: &Scene,
+    graph: &Graph,
+    rail: &Rail,
+    exit: usize,
+    rail_exit: &RailExit,
+) -> Option<Problem> {
+    let Some(node) = graph.rail_exit_node(scene, rail_exit) else {
+        return Some(Problem::RailExitWithoutTile {
+            rail: rail.index,
+            exit,
+        });
+    };
+    let Some(side) = Direction::hex_rotation_to_direction(rail_exit.side_hex_rotation) else {
+        return Some(Problem::InvalidRailSide {
+            rail: rail.index,
+            exit,
+            side_hex_rotation: rail_exit.side_hex_rotation,
+        });
+    };
+    let node = &graph.nodes[node];
+    if catalogue::tile(&node.kind).is_open(side, node.hex_rotation) {
+        None
+    } else {
+        Some(Problem::RailExitClosed {
+            rail: rail.index,
+            exit,
+            tile: node.kind.clone(),
+            side,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Problem, ValidationReport, overlapping_tiles};
+    use crate::app::course::{Corner, Course, Direction, HexVector, SavedCourse, VertexVector};
+    use crate::app::fixtures::skytrax_course;
+    use crate::app::fixtures::{tile, tower_course, tower_scene};
+    use crate::app::layer::{TileKind, TileTowerConstructionData, TileTowerTreeNodeData};
+    use crate::app::scene::{self, CourseElement};
+
+    #[test]
+    fn rails_are_checked_against_the_tiles_they_end_on() {
+        let course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+        let report = ValidationReport::from(&course);
+
+        // The rail leaves the east side of the curve which is closed at rotation 3, and ends on
+        // the large layer where there is no tile
+        let [closed, missing] = report.problems.as_slice() else {
+            panic!("expected two problems, got {:?}", report.problems);
+        };
+        assert!(matches!(
+            closed,
+            Problem::RailExitClosed {
+                rail: 0,
+                exit: 1,
+                tile: TileKind::Curve,
+                side: Direction::East,
+            }
+        ));
+        assert_eq!(
+            closed.to_string(),
+            "Exit 1 of rail [0] is on the closed East side of [Curve]"
+        );
+        assert!(matches!(
+            missing,
+            Problem::RailExitWithoutTile { rail: 0, exit: 2 }
+        ));
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn broken_courses_report_every_problem() {
+        let mut course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+        let Course::LightStones2023(inner) = &mut course else {
+            panic!("expected a LightStones2023 course");
+        };
+        inner.rail_construction_data[0]
+            .exit_2_identifier
+            .retainer_id = 7;
+        inner.wall_construction_data[0]
+            .lower_stacker_tower_2_local_hex_pos
+            .y = -6;
+        let tower = &mut inner.layer_construction_data[0].cell_construction_datas[0].tree_node_data;
+        tower.children[0].construction_data.kind = TileKind::Unknown(999);
+        tower.children.push(TileTowerTreeNodeData {
+            index: 2,
+            construction_data: TileTowerConstructionData {
+                kind: TileKind::Cross,
+                height_in_small_stacker: 1,
+                hex_rotation: 0,
+                retainer_id: None,
+                power_signal_mode: None,
+                light_stone_color_mode: None,
+            },
+            children: Vec::new(),
+        });
+
+        let report = ValidationReport::from(&course);
+        let problems: Vec<_> = report.problems.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            problems,
+            [
+                "Rail [0] refers to unknown retainer [7]",
+                "Tile at [3, 3] has unknown kind [999]",
+                "Tiles [Unknown(999)] and [Cross] at [3, 3] overlap at height 17",
+                "Wall [0] spans 5 fields",
+            ]
+        );
+        assert!(matches!(
+            report.problems[0],
+            Problem::Scene(scene::Error::UnknownRetainerError {
+                element: CourseElement::Rail(0),
+                retainer_id: 7
+            })
+        ));
+    }
+
+    #[test]
+    fn tiles_overlap_retainers() {
+        // A cross standing in the cell of the stacker tower, within its height
+        let mut scene = tower_scene();
+        scene.cells[0].tiles.push(tile(TileKind::Cross, 5));
+        let problems: Vec<_> = overlapping_tiles(&scene.cells[0])
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            ["Tiles [StackerTowerClosed] and [Cross] at [3, 3] overlap at height 5"]
+        );
+
+        // Tiles on a double balcony are at its height, but not in its way
+        let mut balcony = tile(TileKind::DoubleBalcony, 4);
+        balcony.retainer_id = Some(300);
+        let mut curve = tile(TileKind::Curve, 4);
+        curve.parent = Some(0);
+        let cell = scene::Cell {
+            retainer_id: 0,
+            position: HexVector::new(0, 0),
+            tiles: vec![balcony, curve],
+        };
+        assert!(overlapping_tiles(&cell).is_empty());
+    }
+
+    #[test]
+    fn walls_have_to_be_straight() {
+        let mut course = SavedCourse::from_bytes(&tower_course()).unwrap().course;
+        let Course::LightStones2023(inner) = &mut course else {
+            panic!("expected a LightStones2023 course");
+        };
+        // One step north west and one west of the first tower is not in line with it
+        let wall = &mut inner.wall_construction_data[0];
+        let first = wall.lower_stacker_tower_1_local_hex_pos.clone();
+        wall.lower_stacker_tower_2_local_hex_pos = first.add(&HexVector::new(1, 1));
+
+        let report = ValidationReport::from(&course);
+        assert!(matches!(
+            report.problems[0],
+            Problem::Scene(scene::Error::WallNotStraightError {
+                element: CourseElement::Wall(0)
+            })
+        ));
+        assert_eq!(
+            report.problems[0].to_string(),
+            "Wall [0] does not run in a straight line between its stacker towers"
+        );
+    }
+
+    #[test]
+    fn connectors_need_two_tiles_at_a_corner() {
+        let mut course = SavedCourse::from_bytes(&skytrax_course().with_checksum())
+            .unwrap()
+            .course;
+        let Course::SkyTrax(inner) = &mut course else {
+            panic!("expected a SkyTrax course");
+        };
+        // At the south corner of the starter's cell, there is no other tile to join it to
+        let vertex = VertexVector::new(&HexVector::new(6, -2), Corner::South);
+        inner.connectors[0].pos_x = vertex.y;
+        inner.connectors[0].pos_y = vertex.x;
+        inner.connectors[0].height = 7;
+
+        let report = ValidationReport::from(&course);
+        let connectors: Vec<_> = report
+            .problems
+            .iter()
+            .filter(|problem| {
+                matches!(
+                    problem,
+                    Problem::ConnectorNotOnVertex { .. } | Problem::LooseConnector { .. }
+                )
+            })
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            connectors,
+            [
+                "Connector [0] joins 1 tiles",
+                "Connector [1] is not at the corner of a cell",
+            ]
+        );
+    }
+}
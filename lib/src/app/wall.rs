@@ -3,8 +3,9 @@ use serde::Serialize;
 
 use crate::app::course::{CourseSaveDataVersion, HexVector};
 use crate::app::layer::CellConstructionData;
+use crate::app::layout::Layout;
 
-#[derive(Debug, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 pub enum WallKind {
     StraightSmall,
     StraightMedium,
@@ -45,20 +46,21 @@ mod tests {
     }
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Clone, Copy, Debug, DekuRead, DekuWrite, Eq, Layout, PartialEq, Serialize)]
 #[deku(id_type = "u32")]
 pub enum WallSide {
     West = 0,
     East = 1,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Clone, Debug, DekuRead, DekuWrite, Layout, Serialize)]
 pub struct WallCoordinate {
     pub column: i32,
     pub row: i32,
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct WallConstructionData {
@@ -67,7 +69,7 @@ pub struct WallConstructionData {
     pub lower_stacker_tower_2_retainer_id: i32,
     pub lower_stacker_tower_2_local_hex_pos: HexVector,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "balcony_construction_datas.len() as i32")]
     balcony_construction_data_size: i32,
 
     #[deku(ctx = "version")]
@@ -75,7 +77,8 @@ pub struct WallConstructionData {
     pub balcony_construction_datas: Vec<WallBalconyConstructionData>,
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct WallBalconyConstructionData {
@@ -83,7 +86,7 @@ pub struct WallBalconyConstructionData {
     pub wall_side: WallSide,
     pub wall_coordinate: WallCoordinate,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "cell_construction_datas.is_some()")]
     has_cell_construction_data: bool,
     #[deku(cond = "*has_cell_construction_data")]
     #[deku(ctx = "version")]
@@ -5,10 +5,11 @@ use crate::app::course::{
     CourseElementGeneration, CourseMetaData, CourseSaveDataVersion, HexVector,
 };
 use crate::app::layer::{LayerKind, TileKind};
+use crate::app::layout::Layout;
 use crate::app::pillar::PillarConstructionData;
 use crate::app::rail::RailConstructionData;
 
-#[derive(Debug, DekuRead, Serialize)]
+#[derive(Clone, Debug, DekuRead, DekuWrite, Eq, Layout, PartialEq, Serialize)]
 #[deku(id_type = "u32")]
 pub enum RopeKind {
     #[deku(id = "0")]
@@ -25,29 +26,30 @@ pub enum RopeKind {
     Unknown(u32),
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 #[deku(ctx = "version: CourseSaveDataVersion")]
 pub struct Course {
     pub meta_data: CourseMetaData,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "layer_construction_data.len() as u32")]
     layer_construction_data_size: u32,
     #[deku(count = "layer_construction_data_size")]
     pub layer_construction_data: Vec<LayerConstructionData>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "rail_construction_data.len() as u32")]
     rail_construction_data_size: u32,
     #[deku(ctx = "version")]
     #[deku(count = "rail_construction_data_size")]
     pub rail_construction_data: Vec<RailConstructionData>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "pillar_construction_data.len() as u32")]
     pillar_construction_data_size: u32,
     #[deku(count = "pillar_construction_data_size")]
     pub pillar_construction_data: Vec<PillarConstructionData>,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "rope_construction_data.len() as u32")]
     rope_construction_data_size: u32,
     #[deku(count = "rope_construction_data_size")]
     pub rope_construction_data: Vec<RopeConstructionData>,
@@ -55,20 +57,22 @@ pub struct Course {
     pub generation: CourseElementGeneration,
 }
 
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 pub struct CellConstructionData {
     pub hex_rotation: i32,
     pub local_hex_position: HexVector,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "tile_kinds.len() as i32")]
     tile_kind_size: i32,
     #[deku(count = "tile_kind_size")]
     pub tile_kinds: Vec<TileKind>,
 }
 
 // TODO: Can this at least be generic over CellConstructionData? Check deku
-#[deku_derive(DekuRead)]
+#[derive(Layout)]
+#[deku_derive(DekuRead, DekuWrite)]
 #[derive(Debug, Serialize)]
 pub struct LayerConstructionData {
     pub layer_id: i32,
@@ -76,14 +80,17 @@ pub struct LayerConstructionData {
     pub layer_height: f32,
     pub hex_vector: HexVector,
 
-    #[deku(temp)]
+    #[deku(temp, temp_value = "cell_construction_data.len() as i32")]
     cell_construction_data_size: i32,
     #[deku(count = "cell_construction_data_size")]
     pub cell_construction_data: Vec<CellConstructionData>,
 }
 
-#[derive(Debug, DekuRead, Serialize)]
+/// The rope of a zipline, from the cell of a [`TileKind::ZiplineStart`] to the cell of a
+/// [`TileKind::ZiplineEnd`].
+#[derive(Debug, DekuRead, DekuWrite, Layout, Serialize)]
 pub struct RopeConstructionData {
+    /// The index of the layer in [`Course::layer_construction_data`], not its id.
     pub start_tile_layer_index: u32,
     pub start_tile_local_hex_pos: HexVector,
     pub end_tile_layer_index: u32,
@@ -0,0 +1,550 @@
+//! What every [`TileKind`] and [`RailKind`] is: how tall it is, whether things can be built on
+//! it, where marbles enter and leave it and which physical pieces it is made of.
+//!
+//! Every kind is listed explicitly, there is no fallback for kinds that were forgotten. The sides
+//! of the tiles are those of the physical pieces. Which of them faces east at rotation 0 follows
+//! the starter (exit to the east) and the curve (east and south west); for the other pieces it
+//! is an assumption that has not been compared with courses built in the app yet. SkyTrax pieces
+//! hand marbles on at their connectors rather than their sides and count as open on every side,
+//! as do kinds this version does not know.
+use serde::Serialize;
+
+use crate::app::course::Direction;
+use crate::app::layer::TileKind;
+use crate::app::rail::RailKind;
+use crate::physical::Element;
+
+/// What a tile is, without the physical pieces (see [`elements`]) so looking it up doesn't
+/// allocate.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct TileSpec {
+    /// How tall the piece is, in small stackers. For pieces reaching above their tile (lifts,
+    /// screws, ...) only the tile counts, the rest stands beside the tiles stacked on the cell.
+    pub height: i32,
+    /// Set for pieces that other tiles are built on and that have a retainer id in the course.
+    pub retainer: Option<RetainerSpec>,
+    /// Where marbles enter and leave the tile at rotation 0, empty for pieces no marble runs
+    /// through sideways (stackers, trampolines, ...).
+    pub openings: &'static [Opening],
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct RetainerSpec {
+    /// How many small stackers higher than the retainer itself tiles on it start.
+    pub build_height: i32,
+}
+
+impl RetainerSpec {
+    /// Used for tiles that have a retainer id in a course but are not known to be retainers,
+    /// the same as a [`TileKind::DoubleBalcony`].
+    pub const UNKNOWN: RetainerSpec = RetainerSpec { build_height: 0 };
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct Opening {
+    /// The side of the tile at rotation 0, turn it with [`Direction::rotated`].
+    pub side: Direction,
+    pub kind: OpeningKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum OpeningKind {
+    Entry,
+    Exit,
+    /// Marbles can run through the side in both directions.
+    Both,
+}
+
+impl OpeningKind {
+    /// Whether marbles can enter the tile through the side.
+    pub fn enters(self) -> bool {
+        matches!(self, OpeningKind::Entry | OpeningKind::Both)
+    }
+
+    /// Whether marbles can leave the tile through the side.
+    pub fn exits(self) -> bool {
+        matches!(self, OpeningKind::Exit | OpeningKind::Both)
+    }
+}
+
+impl TileSpec {
+    /// How marbles run through `side` when the tile is turned by `hex_rotation`, `None` if the
+    /// side is closed.
+    pub fn opening(&self, side: Direction, hex_rotation: i32) -> Option<OpeningKind> {
+        let mut kinds = self
+            .openings
+            .iter()
+            .filter(|opening| opening.side.rotated(hex_rotation) == side)
+            .map(|opening| opening.kind);
+        let first = kinds.next()?;
+        // Pieces like vertical cannons take marbles in and let them out on the same side at
+        // different heights
+        Some(if kinds.any(|kind| kind != first) {
+            OpeningKind::Both
+        } else {
+            first
+        })
+    }
+
+    /// Whether marbles can enter or leave the tile through `side` when the tile is turned by
+    /// `hex_rotation`.
+    pub fn is_open(&self, side: Direction, hex_rotation: i32) -> bool {
+        self.opening(side, hex_rotation).is_some()
+    }
+
+    /// Whether no marble runs through a side of the tile, e.g. stackers and stacker towers.
+    pub fn is_closed(&self) -> bool {
+        self.openings.is_empty()
+    }
+}
+
+const fn both(side: Direction) -> Opening {
+    Opening {
+        side,
+        kind: OpeningKind::Both,
+    }
+}
+
+const fn entry(side: Direction) -> Opening {
+    Opening {
+        side,
+        kind: OpeningKind::Entry,
+    }
+}
+
+const fn exit(side: Direction) -> Opening {
+    Opening {
+        side,
+        kind: OpeningKind::Exit,
+    }
+}
+
+const CLOSED: &[Opening] = &[];
+/// Marbles start or land on the piece and leave it through one side.
+const EXIT_ONLY: &[Opening] = &[exit(Direction::East)];
+/// Marbles run in and leave downwards or through the air, e.g. to a catch.
+const ENTRY_ONLY: &[Opening] = &[entry(Direction::West)];
+/// Marbles run through in one direction.
+const THROUGH: &[Opening] = &[entry(Direction::West), exit(Direction::East)];
+const CURVE: &[Opening] = &[both(Direction::East), both(Direction::SouthWest)];
+const STRAIGHT: &[Opening] = &[both(Direction::East), both(Direction::West)];
+const CROSS: &[Opening] = &[
+    both(Direction::East),
+    both(Direction::West),
+    both(Direction::SouthEast),
+    both(Direction::NorthWest),
+];
+const GOAL_BASIN: &[Opening] = &[
+    entry(Direction::NorthEast),
+    entry(Direction::East),
+    entry(Direction::SouthEast),
+    entry(Direction::SouthWest),
+    entry(Direction::West),
+    entry(Direction::NorthWest),
+];
+const ALL_SIDES: &[Opening] = &[
+    both(Direction::NorthEast),
+    both(Direction::East),
+    both(Direction::SouthEast),
+    both(Direction::SouthWest),
+    both(Direction::West),
+    both(Direction::NorthWest),
+];
+const SWITCH: &[Opening] = &[
+    entry(Direction::West),
+    exit(Direction::NorthEast),
+    exit(Direction::SouthEast),
+];
+/// The switch without its insert, marbles can also be merged with it.
+const TWO_WAY: &[Opening] = &[
+    both(Direction::West),
+    both(Direction::NorthEast),
+    both(Direction::SouthEast),
+];
+const TWO_IN_ONE_LEFT: &[Opening] = &[
+    entry(Direction::NorthWest),
+    entry(Direction::West),
+    exit(Direction::East),
+];
+const THREE_IN_ONE: &[Opening] = &[
+    entry(Direction::NorthWest),
+    entry(Direction::West),
+    entry(Direction::SouthWest),
+    exit(Direction::East),
+];
+const FUNNEL: &[Opening] = &[entry(Direction::East), entry(Direction::West)];
+const THREE_ENTRANCE_FUNNEL: &[Opening] = &[
+    entry(Direction::NorthEast),
+    entry(Direction::SouthEast),
+    entry(Direction::West),
+];
+/// Marbles fall in from above and leave through one of three sides.
+const SPLASH: &[Opening] = &[
+    exit(Direction::East),
+    exit(Direction::SouthWest),
+    exit(Direction::NorthWest),
+];
+const MIXER: &[Opening] = &[
+    entry(Direction::West),
+    exit(Direction::NorthEast),
+    exit(Direction::East),
+    exit(Direction::SouthEast),
+];
+const TURN_LEFT: &[Opening] = &[entry(Direction::West), exit(Direction::NorthEast)];
+const TURN_RIGHT: &[Opening] = &[entry(Direction::West), exit(Direction::SouthEast)];
+const DROPDOWN_SWITCH_LEFT: &[Opening] = &[
+    entry(Direction::West),
+    exit(Direction::NorthEast),
+    exit(Direction::East),
+];
+const DROPDOWN_SWITCH_RIGHT: &[Opening] = &[
+    entry(Direction::West),
+    exit(Direction::East),
+    exit(Direction::SouthEast),
+];
+/// Two small curves (between neighbouring sides) or two curves next to each other.
+const TWO_CURVES_A: &[Opening] = &[
+    both(Direction::East),
+    both(Direction::SouthEast),
+    both(Direction::West),
+    both(Direction::NorthWest),
+];
+const TWO_CURVES_B: &[Opening] = &[
+    both(Direction::NorthEast),
+    both(Direction::East),
+    both(Direction::SouthWest),
+    both(Direction::West),
+];
+/// A straight from west to east crossed by a curve from north east to south east.
+const STRAIGHT_CURVE_CROSSING: &[Opening] = &[
+    both(Direction::NorthEast),
+    both(Direction::East),
+    both(Direction::SouthEast),
+    both(Direction::West),
+];
+const SLOPE_CURVE: &[Opening] = &[entry(Direction::SouthWest), exit(Direction::East)];
+const BOOMERANG: &[Opening] = &[entry(Direction::West), exit(Direction::NorthWest)];
+
+/// Vertical cannons and space tubes take marbles in at the bottom (west) and let them out at the
+/// top, the variants differ in the side of the top exit.
+const fn shoot_up(top: Direction) -> [Opening; 2] {
+    [entry(Direction::West), exit(top)]
+}
+
+const VERTICAL_CANNON_0: &[Opening] = &shoot_up(Direction::East);
+const VERTICAL_CANNON_60: &[Opening] = &shoot_up(Direction::SouthEast);
+const VERTICAL_CANNON_120: &[Opening] = &shoot_up(Direction::SouthWest);
+const VERTICAL_CANNON_180: &[Opening] = &shoot_up(Direction::West);
+const VERTICAL_CANNON_240: &[Opening] = &shoot_up(Direction::NorthWest);
+const VERTICAL_CANNON_300: &[Opening] = &shoot_up(Direction::NorthEast);
+
+/// Looks up a tile.
+pub fn tile(kind: &TileKind) -> TileSpec {
+    // A stacker tower is 14 small stackers tall (seven large stackers), so anything built on top
+    // of it starts 14 small stackers higher.
+    const TOWER: Option<RetainerSpec> = Some(RetainerSpec { build_height: 14 });
+    // A light base is 4 small stackers tall.
+    const LIGHT_BASE: Option<RetainerSpec> = Some(RetainerSpec { build_height: 4 });
+    // Tiles built on releasers and vertical cannons start on top of the piece.
+    const RELEASER: Option<RetainerSpec> = Some(RetainerSpec { build_height: 2 });
+    const VERTICAL_CANNON: Option<RetainerSpec> = Some(RetainerSpec { build_height: 4 });
+
+    let (height, retainer, openings) = match kind {
+        TileKind::None => (0, None, CLOSED),
+
+        TileKind::Stacker | TileKind::LightStacker => (2, None, CLOSED),
+        TileKind::StackerSmall | TileKind::LightStackerSmall => (1, None, CLOSED),
+        // Batches are the stacks the app shows as one piece, their size is not stored
+        TileKind::StackerBatch | TileKind::LightStackerBatch => (0, None, CLOSED),
+        TileKind::StackerTowerClosed | TileKind::StackerTowerOpened => (14, TOWER, CLOSED),
+        TileKind::LightBase => (4, LIGHT_BASE, CLOSED),
+        // A double balcony is one small stacker tall but holds its tiles at its own height.
+        TileKind::DoubleBalcony => (1, Some(RetainerSpec::UNKNOWN), CLOSED),
+
+        // The variants of these are the same piece set up or turned differently. Their heights
+        // are unverified, neither measured on the pieces nor checked against a course from the
+        // app. The tests only hold the bill of materials to them (`retainer_course`).
+        TileKind::Releaser1 | TileKind::Releaser2 | TileKind::Releaser3 | TileKind::Releaser4 => {
+            (2, RELEASER, EXIT_ONLY)
+        }
+        TileKind::VerticalCannon0 => (4, VERTICAL_CANNON, VERTICAL_CANNON_0),
+        TileKind::VerticalCannon60 => (4, VERTICAL_CANNON, VERTICAL_CANNON_60),
+        TileKind::VerticalCannon120 => (4, VERTICAL_CANNON, VERTICAL_CANNON_120),
+        TileKind::VerticalCannon180 => (4, VERTICAL_CANNON, VERTICAL_CANNON_180),
+        TileKind::VerticalCannon240 => (4, VERTICAL_CANNON, VERTICAL_CANNON_240),
+        TileKind::VerticalCannon300 => (4, VERTICAL_CANNON, VERTICAL_CANNON_300),
+        TileKind::SpaceTubeAligned => (4, VERTICAL_CANNON, VERTICAL_CANNON_0),
+        TileKind::SpaceTubeUnaligned => (4, VERTICAL_CANNON, VERTICAL_CANNON_60),
+
+        TileKind::Starter | TileKind::DomeStarter | TileKind::Catch | TileKind::ZiplineEnd => {
+            (1, None, EXIT_ONLY)
+        }
+        TileKind::GoalBasin | TileKind::FinishArena => (1, None, GOAL_BASIN),
+        TileKind::GoalRail
+        | TileKind::FinishTrigger
+        | TileKind::Drop
+        | TileKind::Catapult
+        | TileKind::Jumper
+        | TileKind::Volcano
+        | TileKind::ZiplineStart => (1, None, ENTRY_ONLY),
+        // Marbles land on trampolines from above and bounce off through the air
+        TileKind::Trampolin0 | TileKind::Trampolin1 | TileKind::Trampolin2 => (1, None, CLOSED),
+
+        TileKind::Curve | TileKind::CurveTunnel | TileKind::RibbonCurve => (1, None, CURVE),
+        TileKind::StraightTunnel | TileKind::Bridge | TileKind::Trigger => (1, None, STRAIGHT),
+        // A marble running into a cannon shoots the one on the other side out, this works in
+        // both directions
+        TileKind::Cannon => (1, None, STRAIGHT),
+        TileKind::Cross | TileKind::ColorSwapEmpty | TileKind::ColorSwapPreloaded => {
+            (1, None, CROSS)
+        }
+        TileKind::Hammer
+        | TileKind::Loop
+        | TileKind::Cascade
+        | TileKind::LiftSmall
+        | TileKind::LiftLarge
+        | TileKind::Flip
+        | TileKind::TipTube
+        | TileKind::Transfer
+        | TileKind::ScrewSmall
+        | TileKind::ScrewMedium
+        | TileKind::ScrewLarge
+        | TileKind::Helix
+        | TileKind::Spinner
+        | TileKind::Queue
+        | TileKind::Lever
+        | TileKind::Elevator
+        | TileKind::ElectricCannon => (1, None, THROUGH),
+
+        TileKind::SwitchLeft
+        | TileKind::SwitchRight
+        | TileKind::SwitchTunnel
+        | TileKind::Splitter => (1, None, SWITCH),
+        TileKind::TwoWay => (1, None, TWO_WAY),
+        TileKind::DropdownSwitchLeft => (1, None, DROPDOWN_SWITCH_LEFT),
+        TileKind::DropdownSwitchRight => (1, None, DROPDOWN_SWITCH_RIGHT),
+        TileKind::DipperLeft => (1, None, TURN_LEFT),
+        TileKind::DipperRight => (1, None, TURN_RIGHT),
+        TileKind::Threeway | TileKind::K3In1Slope => (1, None, THREE_IN_ONE),
+        TileKind::K2In1Slope => (1, None, TWO_IN_ONE_LEFT),
+        TileKind::Spiral => (1, None, FUNNEL),
+        TileKind::ThreeEntranceFunnel => (1, None, THREE_ENTRANCE_FUNNEL),
+        TileKind::Splash => (1, None, SPLASH),
+        TileKind::MixerOffsetExits
+        | TileKind::MixerSameExits
+        | TileKind::CarouselSameExits
+        | TileKind::CarouselOffsetExits => (1, None, MIXER),
+        TileKind::Turntable | TileKind::MultiJunction | TileKind::TripleSmallCurve => {
+            (1, None, ALL_SIDES)
+        }
+
+        TileKind::TwoInOneSmallCurveA
+        | TileKind::FlexibleTwoInOneA
+        | TileKind::DoubleSmallCurve => (1, None, TWO_CURVES_A),
+        TileKind::TwoInOneSmallCurveB
+        | TileKind::FlexibleTwoInOneB
+        | TileKind::CurveCrossing
+        | TileKind::DoubleBigCurve
+        | TileKind::K120DoubleCurveSlope => (1, None, TWO_CURVES_B),
+        TileKind::StraightCurveCrossing => (1, None, STRAIGHT_CURVE_CROSSING),
+        TileKind::KCrossingSlope | TileKind::KJumpCrossingSlope => (1, None, CROSS),
+        TileKind::KCurveSlope1 | TileKind::KCurveSlope2 => (1, None, SLOPE_CURVE),
+        TileKind::KBoomerangSlope => (1, None, BOOMERANG),
+
+        TileKind::Kst2In1L
+        | TileKind::Kst2In1R
+        | TileKind::Kst120CatchDrop60L
+        | TileKind::Kst120CatchDrop60R
+        | TileKind::Kst180Catch6060
+        | TileKind::KstCrossingCatchDrop
+        | TileKind::KstCurveCatch
+        | TileKind::KstCurveDrop
+        | TileKind::KstFinish
+        | TileKind::KstGtDrop
+        | TileKind::KstHs5
+        | TileKind::KstHs20
+        | TileKind::KstMultiCatchDrop
+        | TileKind::KstMultiCatcher
+        | TileKind::KstSpiral120CatchDropCatchL
+        | TileKind::KstSpiral120CatchDropCatchR
+        | TileKind::KstSpiral180CatchDropL
+        | TileKind::KstSpiral180CatchDropR
+        | TileKind::KstSpiral240CatchL
+        | TileKind::KstSpiral240CatchR
+        | TileKind::KstSpiral300L
+        | TileKind::KstSpiral300R
+        | TileKind::KstStarter
+        | TileKind::Kst3In1 => (1, None, ALL_SIDES),
+
+        TileKind::Unknown(_) => (1, None, ALL_SIDES),
+    };
+
+    TileSpec {
+        height,
+        retainer,
+        openings,
+    }
+}
+
+/// The physical pieces a tile is built from.
+pub fn elements(kind: &TileKind) -> Vec<Element> {
+    Element::elements_for_tilekind(kind)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RailSpec {
+    /// Straight rails come in different lengths, the length follows from the distance between
+    /// the exits.
+    pub straight: bool,
+    /// How far the rail rises above the higher of its exits, in small stackers.
+    pub height: i32,
+    /// How far the rail bends, in sixths of a full turn and in either direction: the side of one
+    /// exit is the opposite of the side of the other one turned this far. A U-turn bends by 3,
+    /// its exits face the same way.
+    pub turn: u8,
+    /// The physical piece, `None` for straight rails as it depends on the length.
+    pub element: Option<Element>,
+}
+
+/// Looks up a rail.
+pub fn rail(kind: &RailKind) -> RailSpec {
+    let (height, turn) = match kind {
+        RailKind::Straight
+        | RailKind::Bernoulli
+        | RailKind::DropValley
+        | RailKind::Narrow
+        | RailKind::Slow
+        | RailKind::BernoulliSmallStraight
+        | RailKind::FlexTube0 => (0, 0),
+        // The hump of a drop hill, an estimate
+        RailKind::DropHill => (1, 0),
+        RailKind::UTurn | RailKind::FlexTube180 => (0, 3),
+        RailKind::BernoulliSmallLeft
+        | RailKind::BernoulliSmallRight
+        | RailKind::KstBernoulliL
+        | RailKind::KstBernoulliR
+        | RailKind::KstSlide60L
+        | RailKind::KstSlide60R
+        | RailKind::FlexTube60
+        | RailKind::FlexTube300 => (0, 1),
+        RailKind::KstSlide120L
+        | RailKind::KstSlide120R
+        | RailKind::FlexTube120
+        | RailKind::FlexTube240 => (0, 2),
+    };
+
+    RailSpec {
+        straight: *kind == RailKind::Straight,
+        height,
+        turn,
+        element: Element::try_from(kind).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OpeningKind, elements, rail, tile};
+    use crate::app::course::Direction;
+    use crate::app::layer::TileKind;
+    use crate::app::layout::{Layout, Type};
+    use crate::app::rail::RailKind;
+    use crate::physical::Element;
+
+    /// Every variant of an enum as read by the parser.
+    fn all<T>() -> Vec<T>
+    where
+        T: Layout + for<'a> TryFrom<&'a [u8], Error = deku::DekuError>,
+    {
+        let Type::Enum(enumeration) = T::layout() else {
+            panic!("{} is not an enum", std::any::type_name::<T>());
+        };
+        enumeration
+            .variants
+            .iter()
+            .map(|(_, id)| T::try_from(id.to_le_bytes().as_slice()).expect("variant parses"))
+            .collect()
+    }
+
+    #[test]
+    fn every_tile_is_catalogued() {
+        let kinds = all::<TileKind>();
+        assert!(kinds.len() > 100);
+        for kind in kinds {
+            let spec = tile(&kind);
+            let carries_tiles = kind.is_stacker()
+                || spec.retainer.is_some_and(|_| spec.openings.is_empty())
+                || kind == TileKind::None;
+            let trampoline = matches!(
+                kind,
+                TileKind::Trampolin0 | TileKind::Trampolin1 | TileKind::Trampolin2
+            );
+            assert_eq!(
+                spec.is_closed(),
+                carries_tiles || trampoline,
+                "openings of {kind:?}"
+            );
+            let batch = matches!(kind, TileKind::StackerBatch | TileKind::LightStackerBatch);
+            assert_eq!(
+                spec.height == 0,
+                batch || kind == TileKind::None,
+                "height of {kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn every_rail_is_catalogued() {
+        let kinds = all::<RailKind>();
+        assert_eq!(kinds.len(), 22);
+        for kind in kinds {
+            let spec = rail(&kind);
+            assert!(spec.turn <= 3, "turn of {kind:?}");
+            assert!(spec.height >= 0, "height of {kind:?}");
+        }
+        assert_eq!(rail(&RailKind::UTurn).turn, 3);
+        assert_eq!(rail(&RailKind::FlexTube240).turn, 2);
+    }
+
+    #[test]
+    fn retainers_know_their_height() {
+        let tower = tile(&TileKind::StackerTowerClosed);
+        assert_eq!(tower.height, 14);
+        assert_eq!(tower.retainer.unwrap().build_height, 14);
+        assert_eq!(tile(&TileKind::LightBase).retainer.unwrap().build_height, 4);
+        assert_eq!(tile(&TileKind::Curve).retainer, None);
+        assert_eq!(elements(&TileKind::Curve), [Element::Curve]);
+
+        // Tiles are built on top of vertical cannons and releasers but marbles run through them
+        let cannon = tile(&TileKind::VerticalCannon60);
+        assert_eq!(cannon.retainer.unwrap().build_height, 4);
+        assert!(!cannon.is_closed());
+        assert_eq!(tile(&TileKind::Releaser2).retainer.unwrap().build_height, 2);
+        assert!(tower.is_closed());
+    }
+
+    #[test]
+    fn openings_turn_with_the_tile() {
+        let curve = tile(&TileKind::Curve);
+        assert!(curve.is_open(Direction::East, 0));
+        assert!(!curve.is_open(Direction::East, 1));
+        assert!(curve.is_open(Direction::SouthEast, 1));
+        assert!(curve.is_open(Direction::West, 1));
+
+        let hammer = tile(&TileKind::Hammer);
+        assert_eq!(hammer.opening(Direction::East, 0), Some(OpeningKind::Exit));
+        assert_eq!(hammer.opening(Direction::East, 3), Some(OpeningKind::Entry));
+        assert_eq!(hammer.opening(Direction::NorthEast, 0), None);
+        assert!(!tile(&TileKind::Stacker).is_open(Direction::West, 0));
+
+        // The top exit of a vertical cannon turned half way is above its entry
+        let cannon = tile(&TileKind::VerticalCannon180);
+        assert_eq!(cannon.opening(Direction::West, 0), Some(OpeningKind::Both));
+    }
+
+    #[test]
+    fn straight_rails_have_no_single_element() {
+        assert!(rail(&RailKind::Straight).straight);
+        assert_eq!(rail(&RailKind::Straight).element, None);
+        assert_eq!(rail(&RailKind::Slow).element, Some(Element::Slow));
+    }
+}
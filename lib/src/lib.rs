@@ -1,4 +1,5 @@
 pub mod app;
+pub mod catalogue;
 pub mod common;
 pub mod gravisheet;
 pub mod physical;
@@ -3,7 +3,7 @@ mod element;
 mod inventory;
 mod set;
 
-pub use bom::BillOfMaterials;
+pub use bom::{BillOfMaterials, Error as BillOfMaterialsError, Explanation};
 pub use element::Element;
 pub use inventory::Inventory;
 pub use set::{Set, SetRepo};
@@ -1,4 +1,5 @@
 use crate::app::BillOfMaterials as AppBillOfMaterials;
+use crate::app::bom::{Explanation as AppExplanation, Item, Source};
 use crate::app::layer::LayerKind;
 use crate::app::rail::RailKind;
 use crate::physical::set::SetRepo;
@@ -97,10 +98,65 @@ impl TryFrom<AppBillOfMaterials> for BillOfMaterials {
             }
         }
 
+        if bom.connectors > 0 {
+            *elements.entry(Element::Connector).or_insert(0) += bom.connectors;
+        }
+
+        // Marbles of any colour can't be told apart from the ones of a specific colour in an
+        // inventory, so only the coloured ones are physical requirements. That includes the
+        // different colours some tiles need, e.g. a preloaded colour swap.
+        for color in bom.marbles.marble_colors() {
+            *elements.entry(color.element()).or_insert(0) += 1;
+        }
+
         Ok(BillOfMaterials { elements })
     }
 }
 
+/// Where the elements of a [`BillOfMaterials`] come from, the physical counterpart to
+/// [`app::bom::Explanation`](crate::app::bom::Explanation).
+#[derive(Debug, Default, Serialize)]
+pub struct Explanation {
+    pub elements: HashMap<Element, Vec<Source>>,
+}
+
+impl TryFrom<&AppExplanation> for Explanation {
+    type Error = Error;
+
+    fn try_from(explanation: &AppExplanation) -> Result<Self, Error> {
+        let mut elements: HashMap<Element, Vec<Source>> = HashMap::new();
+
+        for source in &explanation.sources {
+            let converted = match &source.item {
+                Item::Layer(layer_kind) => {
+                    vec![
+                        Element::try_from(layer_kind).context(UnknownLayerKindSnafu {
+                            layer_kind: layer_kind.clone(),
+                        })?,
+                    ]
+                }
+                Item::Tile(tile_kind) => Element::elements_for_tilekind(tile_kind),
+                Item::Rail(rail_kind) => {
+                    vec![Element::try_from(rail_kind).context(UnknownRailKindSnafu {
+                        rail_kind: rail_kind.clone(),
+                    })?]
+                }
+                Item::StraightRail(1) => vec![Element::StraightSmall],
+                Item::StraightRail(2) => vec![Element::StraightMedium],
+                Item::StraightRail(_) => vec![Element::StraightLarge],
+                Item::Wall(wall_kind) => vec![Element::from(wall_kind)],
+                Item::Balcony => vec![Element::Balcony],
+                Item::Connector => vec![Element::Connector],
+            };
+            for element in converted {
+                elements.entry(element).or_default().push(source.clone());
+            }
+        }
+
+        Ok(Explanation { elements })
+    }
+}
+
 impl BillOfMaterials {
     /// This sums up all elements from an inventory
     pub fn from_inventory(
@@ -156,6 +212,9 @@ impl BillOfMaterials {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::fixtures::tower_scene;
+    use crate::app::marbles::{MarbleColor, MarbleRequirement};
+    use std::collections::BTreeSet;
 
     /// Straight rails (counted by length in the app BOM) and wall balconies must
     /// appear in the physical bill of materials; previously both were dropped.
@@ -178,6 +237,43 @@ mod tests {
         assert_eq!(phys.elements.get(&Element::Bernoulli), Some(&5));
     }
 
+    /// Marbles of a specific colour are physical requirements, any other marbles are not.
+    #[test]
+    fn coloured_marbles_are_counted() {
+        let app = AppBillOfMaterials {
+            marbles: MarbleRequirement {
+                total: 5,
+                colors: BTreeSet::from([MarbleColor::Red, MarbleColor::Blue]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let phys = BillOfMaterials::try_from(app).expect("converts");
+        assert_eq!(phys.elements.get(&Element::MarbleRed), Some(&1));
+        assert_eq!(phys.elements.get(&Element::MarbleBlue), Some(&1));
+        assert!(!phys.elements.contains_key(&Element::MarbleGreen));
+    }
+
+    /// Tiles needing marbles of different colours get them even if no colour is given.
+    #[test]
+    fn distinct_colours_are_counted() {
+        let app = AppBillOfMaterials {
+            marbles: MarbleRequirement {
+                total: 2,
+                colors: BTreeSet::from([MarbleColor::Blue]),
+                distinct_colors: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let phys = BillOfMaterials::try_from(app).expect("converts");
+        assert_eq!(phys.elements.get(&Element::MarbleBlue), Some(&1));
+        assert_eq!(phys.elements.get(&Element::MarbleRed), Some(&1));
+        assert!(!phys.elements.contains_key(&Element::MarbleGreen));
+    }
+
     /// Zero straight rails / balconies must not create spurious zero entries.
     #[test]
     fn no_spurious_zero_entries() {
@@ -185,4 +281,24 @@ mod tests {
         assert!(!phys.elements.contains_key(&Element::StraightSmall));
         assert!(!phys.elements.contains_key(&Element::Balcony));
     }
+
+    /// Every element of the physical bill of materials knows where it comes from.
+    #[test]
+    fn explains_elements() {
+        let (app, explanation) = AppBillOfMaterials::explained(&tower_scene());
+        let phys = BillOfMaterials::try_from(app).expect("converts");
+        let explanation = Explanation::try_from(&explanation).expect("converts");
+
+        for (element, count) in &phys.elements {
+            let explained: i32 = explanation
+                .elements
+                .get(element)
+                .into_iter()
+                .flatten()
+                .map(|source| source.count)
+                .sum();
+            assert_eq!(explained, *count, "{element:?}");
+        }
+        assert_eq!(explanation.elements[&Element::Balcony].len(), 1);
+    }
 }
@@ -1,14 +1,16 @@
 //! Corpus oracle: parse every real course fixture in `tests/test-data/`.
 //!
-//! This guards the binary parser. It does three things:
+//! This guards the binary parser. It does four things:
 //!  1. Prove every known good course still parses, catching accidental
 //!     breakage when an enum or a struct layout is edited.
 //!  2. Report the distribution of `CourseSaveDataVersion` across the corpus.
 //!  3. Surface any `Unknown(_)` tags, a value from a newer app release that
 //!     parsed but has no name.
+//!  4. Report how many courses have a valid, wrong or missing checksum.
 //!
 //! A parse failure means the on-disk layout of that save version is not
-//! modelled by the parser.
+//! modelled by the parser. Failures are reported with the offset, the path to
+//! the field and the bytes around it.
 //!
 //! Run with `cargo test -p murmelbahn-lib --test parse_all -- --nocapture` to
 //! see the summary.
@@ -48,7 +50,8 @@ fn count_unknowns(debug: &str, out: &mut BTreeMap<u64, usize>) {
 fn all_fixtures_parse() {
     let mut versions: BTreeMap<String, usize> = BTreeMap::new();
     let mut unknown_tags: BTreeMap<u64, usize> = BTreeMap::new();
-    let mut failures: Vec<(String, Option<u32>, String)> = Vec::new();
+    let mut integrity: BTreeMap<String, usize> = BTreeMap::new();
+    let mut failures: Vec<(String, Option<u32>, String, String)> = Vec::new();
     let mut total = 0usize;
 
     let dir = test_data_dir();
@@ -71,12 +74,18 @@ fn all_fixtures_parse() {
                 *versions
                     .entry(format!("{:?}", course.header.version))
                     .or_default() += 1;
+                *integrity
+                    .entry(format!("{:?}", course.integrity))
+                    .or_default() += 1;
                 count_unknowns(&format!("{course:?}"), &mut unknown_tags);
             }
             Err(e) => failures.push((
                 name,
                 SaveDataHeader::peek_version_raw(&bytes),
                 e.to_string(),
+                e.diagnostics()
+                    .map(|diagnostics| diagnostics.window.to_string())
+                    .unwrap_or_default(),
             )),
         }
     }
@@ -86,6 +95,23 @@ fn all_fixtures_parse() {
     for (version, count) in &versions {
         eprintln!("  {version}: {count}");
     }
+    // The layouts of these versions are only as good as the files they were checked against
+    let missing: Vec<_> = CourseSaveDataVersion::ALL
+        .iter()
+        .filter(|version| version.is_supported())
+        .map(|version| format!("{version:?}"))
+        .filter(|version| !versions.contains_key(version))
+        .collect();
+    if !missing.is_empty() {
+        eprintln!(
+            "supported versions without a fixture (layout unverified): {}",
+            missing.join(", ")
+        );
+    }
+    eprintln!("checksums:");
+    for (state, count) in &integrity {
+        eprintln!("  {state}: {count}");
+    }
     if unknown_tags.is_empty() {
         eprintln!("unknown tags: none");
     } else {
@@ -96,7 +122,7 @@ fn all_fixtures_parse() {
     }
     if !failures.is_empty() {
         eprintln!("FAILURES ({}):", failures.len());
-        for (name, raw_version, err) in &failures {
+        for (name, raw_version, err, window) in &failures {
             let hint = match raw_version {
                 Some(v) => match CourseSaveDataVersion::try_from(v.to_le_bytes().as_slice()) {
                     // Header version is one we recognise but couldn't decode the
@@ -112,6 +138,9 @@ fn all_fixtures_parse() {
                 None => "version unreadable (truncated?)".to_string(),
             };
             eprintln!("  {name}: {hint} :: {err}");
+            for line in window.lines() {
+                eprintln!("    {line}");
+            }
         }
     }
 
@@ -0,0 +1,61 @@
+//! Write oracle: every course fixture in `tests/test-data/` that parses and has
+//! a valid checksum must be written back byte for byte, including the trailing
+//! sha256 checksum. Courses with a missing or wrong checksum can't round-trip
+//! as a fresh checksum is always written.
+//!
+//! This guards the write path. A mismatch means a field is read but not
+//! written back the same way (a `map` without a matching `writer`, or a temp
+//! count whose `temp_value` is wrong).
+
+use murmelbahn_lib::app::course::{Integrity, SavedCourse};
+use std::{fs, path::PathBuf};
+
+#[test]
+fn every_course_round_trips() {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("tests/test-data");
+    if !dir.exists() {
+        eprintln!("tests/test-data not present, skipping (local-only fixtures)");
+        return;
+    }
+
+    let mut failures = Vec::new();
+    for entry in fs::read_dir(&dir).expect("read tests/test-data").flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let bytes = fs::read(&path).expect("read fixture");
+        let Ok(course) = SavedCourse::from_bytes(&bytes) else {
+            continue; // parse failures are the parse oracle's job, not this test's
+        };
+        if course.integrity != Integrity::Valid {
+            continue;
+        }
+
+        match course.to_bytes() {
+            Ok(written) if written == bytes => {}
+            Ok(written) => {
+                let first_difference = written
+                    .iter()
+                    .zip(bytes.iter())
+                    .position(|(a, b)| a != b)
+                    .unwrap_or(written.len().min(bytes.len()));
+                failures.push(format!(
+                    "{name}: differs at byte {first_difference} (wrote {} bytes, read {})",
+                    written.len(),
+                    bytes.len()
+                ));
+            }
+            Err(e) => failures.push(format!("{name}: {e}")),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} course(s) did not round-trip:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
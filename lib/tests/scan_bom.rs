@@ -1,5 +1,5 @@
 //! Bill-of-materials robustness: computing the BOM for every fixture must not
-//! panic. The parse oracle (parse_all) only deserializes courses; this exercises
+//! panic or fail. The parse oracle (parse_all) only deserializes courses; this exercises
 //! the heavier BOM path (rail-length and retainer-height resolution), which is
 //! where a malformed or unhandled course would crash.
 
@@ -30,17 +30,19 @@ fn every_course_computes_a_bill_of_materials_without_panicking() {
         let Ok(course) = SavedCourse::from_bytes(&bytes) else {
             continue; // parse failures are the parse oracle's job, not this test's
         };
-        let result = catch_unwind(AssertUnwindSafe(|| {
-            let _ = BillOfMaterials::from(course.course);
-        }));
-        if result.is_err() {
-            failures.push(path.file_name().unwrap().to_string_lossy().into_owned());
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        match catch_unwind(AssertUnwindSafe(|| {
+            BillOfMaterials::try_from(course.course)
+        })) {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => failures.push(format!("{name}: {e}")),
+            Err(_) => failures.push(format!("{name}: panicked")),
         }
     }
 
     std::panic::set_hook(previous_hook);
     assert!(
         failures.is_empty(),
-        "bill of materials panicked for: {failures:?}"
+        "bill of materials failed for: {failures:?}"
     );
 }
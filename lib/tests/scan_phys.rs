@@ -29,7 +29,10 @@ fn physical_bom_conversion_never_panics() {
         let Ok(course) = SavedCourse::from_bytes(&fs::read(&path).expect("read fixture")) else {
             continue;
         };
-        let app = AppBom::from(course.course);
+        // Failing to resolve the course is scan_bom's job, not this test's.
+        let Ok(app) = AppBom::try_from(course.course) else {
+            continue;
+        };
         // try_from may legitimately return Err (unmapped element); only a panic is a bug.
         if catch_unwind(AssertUnwindSafe(|| {
             let _ = PhysBom::try_from(app);
@@ -1,20 +1,25 @@
 use crate::AppState;
-use crate::api::course::Error::CourseNotFound;
 use axum::Json;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::http::{StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use csv::Writer;
 use metrics::counter;
 use murmelbahn_lib::app::BillOfMaterials;
-use murmelbahn_lib::app::course::SavedCourse;
+use murmelbahn_lib::app::bom::Explanation;
+use murmelbahn_lib::app::course::{Course, Direction, SavedCourse};
+use murmelbahn_lib::app::gltf::Model;
+use murmelbahn_lib::app::scene::Scene;
+use murmelbahn_lib::app::svg;
+use murmelbahn_lib::app::validation::ValidationReport;
 use murmelbahn_lib::common::CourseCode;
 use murmelbahn_lib::gravisheet::GraviSheetOutput;
-use serde::Deserialize;
+use murmelbahn_lib::physical;
+use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 use std::string::FromUtf8Error;
 use std::sync::Arc;
-use tracing::debug;
+use tracing::{debug, info};
 
 // csv::IntoInnerError<Writer<Vec<u8>>> is large (384 bytes). Boxing it would require
 // manual From impls that defeat snafu's context(false), so we allow the lint here.
@@ -27,6 +32,20 @@ pub enum Error {
         source: murmelbahn_lib::app::course::Error,
     },
 
+    #[snafu(display("Failed to resolve course [{course_code}]: {source}"))]
+    InvalidCourse {
+        course_code: CourseCode,
+        source: murmelbahn_lib::app::scene::Error,
+    },
+
+    #[snafu(display(
+        "Failed to convert the bill of materials of course [{course_code}]: {source}"
+    ))]
+    PhysicalBillOfMaterials {
+        course_code: CourseCode,
+        source: physical::BillOfMaterialsError,
+    },
+
     #[snafu(display("Error in CourseRepo"))]
     #[snafu(context(false))]
     CourseRepo { source: crate::course_repo::Error },
@@ -51,11 +70,45 @@ pub enum Error {
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        tracing::error!("{:?}", self);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+        match self {
+            Error::CourseNotFound { .. } => {
+                debug!("{self}");
+                (StatusCode::NOT_FOUND, self.to_string()).into_response()
+            }
+            // A corrupted course is not our fault, tell the client what is wrong with it
+            Error::InvalidCourse { .. } => {
+                info!("{self}");
+                (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()).into_response()
+            }
+            _ => {
+                tracing::error!("{:?}", self);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+            }
+        }
     }
 }
 
+/// Fetches the bytes of a course, [`Error::CourseNotFound`] if there is none with this code.
+async fn load_course_bytes(state: &AppState, course_code: &CourseCode) -> Result<Vec<u8>, Error> {
+    state
+        .course_repo
+        .get_course_bytes(course_code)
+        .await?
+        .context(CourseNotFoundSnafu {
+            course_code: course_code.clone(),
+        })
+}
+
+/// Fetches and parses a course.
+async fn load_course(state: &AppState, course_code: &CourseCode) -> Result<Course, Error> {
+    let course_bytes = load_course_bytes(state, course_code).await?;
+    Ok(SavedCourse::from_bytes(&course_bytes)
+        .context(DeserializationFailedSnafu {
+            course_code: course_code.clone(),
+        })?
+        .course)
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BomFormat {
@@ -67,34 +120,43 @@ pub enum BomFormat {
 #[derive(Default, Deserialize)]
 pub(crate) struct BomParams {
     format: Option<BomFormat>,
+    /// Lists where every element comes from instead of just counting them, always as JSON
+    explain: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ExplainedBom {
+    bom: BillOfMaterials,
+    explanation: Explanation,
+    elements: physical::Explanation,
 }
 
-// TODO: Needs to return a 404
 pub(crate) async fn course_bom(
     Path(course): Path<String>,
-    Query(BomParams { format }): Query<BomParams>,
+    Query(BomParams { format, explain }): Query<BomParams>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Response, Error> {
     counter!("murmelbahn.bom.requests").increment(1);
     let course_code = CourseCode::new(course);
     debug!("Request for BOM for course [{course_code}]");
 
-    let course_bytes = state.course_repo.get_course_bytes(&course_code).await?;
-    let Some(course_bytes) = course_bytes else {
-        return Ok((
-            StatusCode::NOT_FOUND,
-            format!("Course [{}] could not be found", course_code),
-        )
-            .into_response());
-    };
-    let course = SavedCourse::from_bytes(&course_bytes)
-        .context(DeserializationFailedSnafu {
-            course_code: course_code.clone(),
-        })?
-        .course;
-
+    let course = load_course(&state, &course_code).await?;
     let title = course.meta_data().title.clone();
-    let bom = BillOfMaterials::from(course);
+    let scene = Scene::try_from(&course).context(InvalidCourseSnafu {
+        course_code: course_code.clone(),
+    })?;
+    let (bom, explanation) = BillOfMaterials::explained(&scene);
+
+    if explain == Some(true) {
+        let elements = physical::Explanation::try_from(&explanation)
+            .context(PhysicalBillOfMaterialsSnafu { course_code })?;
+        return Ok(Json(ExplainedBom {
+            bom,
+            explanation,
+            elements,
+        })
+        .into_response());
+    }
 
     Ok(match format {
         Some(BomFormat::Csv) => {
@@ -111,25 +173,143 @@ pub(crate) async fn course_bom(
     })
 }
 
+/// Lists the structural problems of a course (e.g. rails ending on closed sides of tiles) in
+/// JSON format.
+///
+/// If the course can't be parsed this returns everything that could be read and where parsing
+/// failed with a 422 status code, like [`course_dump`].
+pub(crate) async fn course_lint(
+    Path(course): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, Error> {
+    counter!("murmelbahn.lint.requests").increment(1);
+    let course_code = CourseCode::new(course);
+    debug!("Request for lint report for course [{course_code}]");
+
+    let course_bytes = load_course_bytes(&state, &course_code).await?;
+    Ok(match SavedCourse::from_bytes_lenient(&course_bytes) {
+        Ok(course) => Json(ValidationReport::from(&course.course)).into_response(),
+        Err(partial) => {
+            info!(
+                "Course [{course_code}] could not be parsed: {}",
+                partial.error
+            );
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(partial)).into_response()
+        }
+    })
+}
+
+/// Exports a course as a binary glTF 3D model.
+pub(crate) async fn course_model(
+    Path(course): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, Error> {
+    counter!("murmelbahn.model.requests").increment(1);
+    let course_code = CourseCode::new(course);
+    debug!("Request for 3D model for course [{course_code}]");
+
+    let course = load_course(&state, &course_code).await?;
+    let model = Model::try_from(&course).context(InvalidCourseSnafu { course_code })?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "model/gltf-binary")],
+        model.to_glb(),
+    )
+        .into_response())
+}
+
+/// Draws a course as seen from above as an SVG image, see [`svg::plan`].
+pub(crate) async fn course_plan(
+    Path(course): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, Error> {
+    counter!("murmelbahn.plan.requests").increment(1);
+    let course_code = CourseCode::new(course);
+    debug!("Request for plan for course [{course_code}]");
+
+    let course = load_course(&state, &course_code).await?;
+    let scene = Scene::try_from(&course).context(InvalidCourseSnafu { course_code })?;
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg::plan(&scene)).into_response())
+}
+
+/// The hex axis an elevation looks along.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Axis {
+    NorthEast,
+    #[default]
+    East,
+    SouthEast,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl From<Axis> for Direction {
+    fn from(axis: Axis) -> Self {
+        match axis {
+            Axis::NorthEast => Direction::NorthEast,
+            Axis::East => Direction::East,
+            Axis::SouthEast => Direction::SouthEast,
+            Axis::SouthWest => Direction::SouthWest,
+            Axis::West => Direction::West,
+            Axis::NorthWest => Direction::NorthWest,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub(crate) struct ElevationParams {
+    /// Looking towards the east if missing
+    axis: Option<Axis>,
+}
+
+/// Draws a course as seen from the side as an SVG image, see [`svg::elevation`].
+pub(crate) async fn course_elevation(
+    Path(course): Path<String>,
+    Query(ElevationParams { axis }): Query<ElevationParams>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, Error> {
+    counter!("murmelbahn.elevation.requests").increment(1);
+    let course_code = CourseCode::new(course);
+    debug!("Request for elevation for course [{course_code}]");
+
+    let course = load_course(&state, &course_code).await?;
+    let scene = Scene::try_from(&course).context(InvalidCourseSnafu { course_code })?;
+    let axis = axis.unwrap_or_default().into();
+
+    Ok((
+        [(header::CONTENT_TYPE, "image/svg+xml")],
+        svg::elevation(&scene, axis),
+    )
+        .into_response())
+}
+
 /// Dumps a course in JSON format
-// TODO: Needs to return a 404
+///
+/// If the course can't be parsed fully (e.g. because it comes from a newer app release) this
+/// returns everything that could be read (e.g. title and version) with a 422 status code.
 pub async fn course_dump(
     Path(course): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<SavedCourse>, Error> {
+) -> Result<Response, Error> {
     counter!("murmelbahn.dump.requests").increment(1);
 
     // Could write a custom Axum extractor at some point
     let course_code = CourseCode::new(course);
-    let course_bytes = state.course_repo.get_course_bytes(&course_code).await?;
+    let course_bytes = load_course_bytes(&state, &course_code).await?;
 
-    let Some(course_bytes) = course_bytes else {
-        return Err(CourseNotFound { course_code });
-    };
-
-    let course = SavedCourse::from_bytes(&course_bytes)
-        .context(DeserializationFailedSnafu { course_code })?;
-    Ok(Json(course))
+    Ok(match SavedCourse::from_bytes_lenient(&course_bytes) {
+        Ok(course) => Json(course).into_response(),
+        Err(partial) => {
+            info!(
+                "Course [{course_code}] could only be parsed partially: {}",
+                partial.error
+            );
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(partial)).into_response()
+        }
+    })
 }
 
 /// Dumps the raw course data as they come from Ravensburger
@@ -142,11 +322,7 @@ pub async fn course_raw_download(
 
     // Could write a custom Axum extractor at some point
     let course_code = CourseCode::new(course);
-    let course_bytes = state.course_repo.get_course_bytes(&course_code).await?;
-
-    let Some(course_bytes) = course_bytes else {
-        return Err(CourseNotFound { course_code });
-    };
+    let course_bytes = load_course_bytes(&state, &course_code).await?;
 
     Ok(course_bytes)
 }
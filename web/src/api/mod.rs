@@ -8,7 +8,10 @@ use axum::routing::{get, post};
 use std::sync::Arc;
 
 use buildable::buildable;
-use course::{course_bom, course_dump, course_raw_download};
+use course::{
+    course_bom, course_dump, course_elevation, course_lint, course_model, course_plan,
+    course_raw_download,
+};
 use set::set_list;
 
 /// Builds the `/api` router (mounted with `nest("/api", ...)` in main).
@@ -16,6 +19,10 @@ pub fn router(state: Arc<AppState>) -> Router {
     let course_routes = Router::new()
         .route("/{id}/dump", get(course_dump))
         .route("/{id}/bom", get(course_bom))
+        .route("/{id}/elevation.svg", get(course_elevation))
+        .route("/{id}/lint", get(course_lint))
+        .route("/{id}/model.glb", get(course_model))
+        .route("/{id}/plan.svg", get(course_plan))
         .route("/{id}/raw", get(course_raw_download))
         .with_state(state.clone());
 
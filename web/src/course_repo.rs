@@ -23,6 +23,11 @@ pub enum Error {
         code: String,
         source: murmelbahn_lib::app::course::Error,
     },
+    #[snafu(display("Failed to resolve course with code '{}': {}", code, source))]
+    InvalidCourse {
+        code: String,
+        source: murmelbahn_lib::app::scene::Error,
+    },
     #[snafu(display("Invalid course metadata: {}", message))]
     InvalidMetadata { message: String },
 }
@@ -176,7 +181,8 @@ impl CourseRepo {
                 .and_then(|saved_course| {
                     let course = saved_course.course;
                     let metadata = course.meta_data().clone();
-                    let app_bom = AppBillOfMaterials::from(course);
+                    let app_bom = AppBillOfMaterials::try_from(course)
+                        .context(InvalidCourseSnafu { code: code.clone() })?;
                     let physical_bom =
                         PhysicalBillOfMaterials::try_from(app_bom).map_err(|_| {
                             Error::InvalidMetadata {